@@ -0,0 +1,14 @@
+#import bevy_pbr::forward_io::VertexOutput
+
+@group(2) @binding(0) var color_texture: texture_2d<f32>;
+@group(2) @binding(1) var color_sampler: sampler;
+@group(2) @binding(2) var detail_texture: texture_2d<f32>;
+@group(2) @binding(3) var detail_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(color_texture, color_sampler, mesh.uv);
+    // The detail texture is tiled so minification (and the lack of mips) is easy to see.
+    let detail = textureSample(detail_texture, detail_sampler, mesh.uv * 8.0).r;
+    return vec4(color.rgb * mix(0.5, 1.0, detail), color.a);
+}
@@ -0,0 +1,181 @@
+//! Derive macro for `bevy_mod_mipmap_generator::GetImages`, use it through the re-export in
+//! `bevy_mod_mipmap_generator`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Field, GenericArgument, Index, Member,
+    PathArguments, Type,
+};
+
+/// Implements `GetImages` by collecting every `Handle<Image>` and `Option<Handle<Image>>` field.
+///
+/// Field attributes:
+/// - `#[get_images(skip)]` leaves the field out.
+/// - `#[get_images(color)]`, `#[get_images(normal)]` and `#[get_images(data)]` report the field's
+///   `TextureRole` in `get_image_roles`. Fields without a role fall back to
+///   `TextureRole::from_format`.
+#[proc_macro_derive(GetImages, attributes(get_images))]
+pub fn derive_get_images(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum FieldKind {
+    Handle,
+    OptionHandle,
+}
+
+struct ImageField {
+    member: Member,
+    kind: FieldKind,
+    role: Option<TokenStream2>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "GetImages can only be derived for structs",
+        ));
+    };
+
+    let mut fields = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let (skip, role) = parse_attributes(field)?;
+        let kind = image_field_kind(&field.ty);
+        if skip {
+            continue;
+        }
+        let Some(kind) = kind else {
+            if role.is_some() {
+                return Err(Error::new_spanned(
+                    &field.ty,
+                    "get_images roles can only be used on Handle<Image> and \
+                    Option<Handle<Image>> fields",
+                ));
+            }
+            continue;
+        };
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        fields.push(ImageField { member, kind, role });
+    }
+
+    let images = fields.iter().map(|field| {
+        let member = &field.member;
+        match field.kind {
+            FieldKind::Handle => quote!(images.push(&self.#member);),
+            FieldKind::OptionHandle => quote!(images.extend(self.#member.as_ref());),
+        }
+    });
+    let roles = fields.iter().filter_map(|field| {
+        let member = &field.member;
+        let role = field.role.as_ref()?;
+        Some(match field.kind {
+            FieldKind::Handle => quote!(roles.push((&self.#member, #role));),
+            FieldKind::OptionHandle => {
+                quote!(roles.extend(self.#member.as_ref().map(|image_h| (image_h, #role)));)
+            }
+        })
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::bevy_mod_mipmap_generator::GetImages for #name #ty_generics
+            #where_clause
+        {
+            fn get_images(&self) -> ::std::vec::Vec<&::bevy::prelude::Handle<::bevy::prelude::Image>> {
+                #[allow(unused_mut)]
+                let mut images = ::std::vec::Vec::new();
+                #(#images)*
+                images
+            }
+
+            fn get_image_roles(
+                &self,
+            ) -> ::std::vec::Vec<(
+                &::bevy::prelude::Handle<::bevy::prelude::Image>,
+                ::bevy_mod_mipmap_generator::TextureRole,
+            )> {
+                #[allow(unused_mut)]
+                let mut roles = ::std::vec::Vec::new();
+                #(#roles)*
+                roles
+            }
+        }
+    })
+}
+
+/// Returns whether the field is skipped and its role, if one is set.
+fn parse_attributes(field: &Field) -> syn::Result<(bool, Option<TokenStream2>)> {
+    let mut skip = false;
+    let mut role = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("get_images"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let new_role = if meta.path.is_ident("skip") {
+                skip = true;
+                return Ok(());
+            } else if meta.path.is_ident("color") {
+                quote!(::bevy_mod_mipmap_generator::TextureRole::Color)
+            } else if meta.path.is_ident("normal") {
+                quote!(::bevy_mod_mipmap_generator::TextureRole::Normal)
+            } else if meta.path.is_ident("data") {
+                quote!(::bevy_mod_mipmap_generator::TextureRole::Data)
+            } else {
+                return Err(meta.error("expected `skip`, `color`, `normal` or `data`"));
+            };
+            if role.replace(new_role).is_some() {
+                return Err(meta.error("only one role can be set"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok((skip, role))
+}
+
+fn image_field_kind(ty: &Type) -> Option<FieldKind> {
+    if is_image_handle(ty) {
+        return Some(FieldKind::Handle);
+    }
+    match single_generic_argument(ty, "Option") {
+        Some(inner) if is_image_handle(inner) => Some(FieldKind::OptionHandle),
+        _ => None,
+    }
+}
+
+fn is_image_handle(ty: &Type) -> bool {
+    matches!(
+        single_generic_argument(ty, "Handle"),
+        Some(Type::Path(inner)) if inner.path.segments.last().is_some_and(|s| s.ident == "Image")
+    )
+}
+
+/// For a type like `name<T>` returns `T`. Only the last path segment is checked, so
+/// `bevy::prelude::Handle<Image>` works too.
+fn single_generic_argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(ty) = ty else {
+        return None;
+    };
+    let segment = ty.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
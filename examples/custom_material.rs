@@ -0,0 +1,121 @@
+//! Generates mipmaps for a custom material by deriving `GetImages`.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+};
+use bevy_mod_mipmap_generator::{
+    generate_mipmaps, GetImages, MipmapGeneratorDebugTextPlugin, MipmapGeneratorPlugin,
+};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, MaterialPlugin::<CustomMaterial>::default()))
+        .add_systems(Startup, setup)
+        // Add MipmapGeneratorPlugin after default plugins
+        .add_plugins((MipmapGeneratorPlugin, MipmapGeneratorDebugTextPlugin))
+        // Add the custom material type to be converted
+        .add_systems(Update, generate_mipmaps::<CustomMaterial>)
+        .run();
+}
+
+#[derive(Asset, TypePath, AsBindGroup, GetImages, Debug, Clone)]
+struct CustomMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    #[get_images(color)]
+    color_texture: Handle<Image>,
+    #[texture(2)]
+    #[sampler(3)]
+    detail_texture: Option<Handle<Image>>,
+}
+
+impl Material for CustomMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/custom_material.wgsl".into()
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CustomMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let material = CustomMaterial {
+        color_texture: images.add(create_color_image(2048)),
+        detail_texture: Some(images.add(create_detail_image(512))),
+    };
+
+    commands.spawn(MaterialMeshBundle {
+        mesh: meshes.add(Plane3d::default().mesh().size(20.0, 20.0)),
+        material: materials.add(material),
+        transform: Transform::from_rotation(Quat::from_rotation_y(PI * 0.25)),
+        ..default()
+    });
+
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.0, 0.5, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn create_color_image(size: u32) -> Image {
+    let data = (0..size * size)
+        .flat_map(|id| {
+            let x = (id % size) as f32 / size as f32;
+            let y = (id / size) as f32 / size as f32;
+            [(x * 255.0) as u8, (y * 255.0) as u8, 128, 255]
+        })
+        .collect();
+    create_image(size, TextureFormat::Rgba8UnormSrgb, data)
+}
+
+fn create_detail_image(size: u32) -> Image {
+    // Fine checkerboard, shimmers badly at a distance without mipmaps
+    let data = (0..size * size)
+        .map(|id| {
+            if ((id % size) / 2 + (id / size) / 2).is_multiple_of(2) {
+                0xFF
+            } else {
+                0x00
+            }
+        })
+        .collect();
+    let mut image = create_image(size, TextureFormat::R8Unorm, data);
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+fn create_image(size: u32, format: TextureFormat, data: Vec<u8>) -> Image {
+    Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                ..default()
+            },
+            dimension: TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        data,
+        ..Default::default()
+    }
+}
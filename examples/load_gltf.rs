@@ -4,7 +4,7 @@ use std::path::PathBuf;
 
 use bevy::prelude::*;
 use bevy_mod_mipmap_generator::{
-    generate_mipmaps, MipmapGeneratorDebugTextPlugin, MipmapGeneratorPlugin,
+    generate_mipmaps, AnisotropicFiltering, MipmapGeneratorDebugTextPlugin, MipmapGeneratorPlugin,
     MipmapGeneratorSettings,
 };
 
@@ -19,8 +19,8 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .insert_resource(MipmapGeneratorSettings {
             // Manually setting anisotropic filtering to 16x
-            anisotropic_filtering: 16,
-            compression: Option::from(use_compression.then(Default::default)),
+            anisotropic_filtering: AnisotropicFiltering::X16,
+            compression: use_compression.then(Default::default),
             compressed_image_data_cache_path: if use_cache {
                 Some(PathBuf::from("compressed_texture_cache"))
             } else {
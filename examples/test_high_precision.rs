@@ -0,0 +1,82 @@
+//! Compares mips generated with and without `high_precision` side by side.
+//! The lower mips of the subtle gradient show more banding without it.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    },
+};
+use bevy_mod_mipmap_generator::{generate_mips_texture, MipmapGeneratorSettings};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let plane_h = meshes.add(Plane3d::default().mesh().size(10.0, 20.0));
+
+    for (x, high_precision) in [(-5.2, false), (5.2, true)] {
+        let mut image = create_test_image(2048);
+        let settings = MipmapGeneratorSettings {
+            high_precision,
+            ..default()
+        };
+        // Generate synchronously so each plane can use its own settings
+        generate_mips_texture(&mut image, &settings, &mut 0).unwrap();
+        let mut material = StandardMaterial::from(images.add(image));
+        material.unlit = true;
+        commands.spawn(PbrBundle {
+            mesh: plane_h.clone(),
+            material: materials.add(material),
+            transform: Transform::from_xyz(x, 0.0, 0.0),
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera3dBundle {
+        // Far away so the lower mips are used
+        transform: Transform::from_xyz(0.0, 1.0, 80.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn create_test_image(size: u32) -> Image {
+    // Dark gradient that only covers a few 8-bit values, plus a fine pattern so the
+    // downsampling has to average across the steps.
+    let data = (0..size * size)
+        .flat_map(|id| {
+            let x = (id % size) as f32 / size as f32;
+            let y = id / size;
+            let v = (x * 24.0 + if (id + y).is_multiple_of(2) { 0.5 } else { 0.0 }) as u8;
+            [v, v, v, u8::MAX]
+        })
+        .collect();
+
+    Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                ..default()
+            },
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        data,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,89 @@
+//! Compares mips generated with and without `post_downsample_sharpen` side by side.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    },
+};
+use bevy_mod_mipmap_generator::{generate_mips_texture, MipmapGeneratorSettings};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let plane_h = meshes.add(Plane3d::default().mesh().size(10.0, 20.0));
+
+    for (x, sharpen) in [(-5.2, None), (5.2, Some(0.5))] {
+        let mut image = create_test_image(2048, -0.8, 0.156);
+        let settings = MipmapGeneratorSettings {
+            post_downsample_sharpen: sharpen,
+            ..default()
+        };
+        // Generate synchronously so each plane can use its own settings
+        generate_mips_texture(&mut image, &settings, &mut 0).unwrap();
+        let mut material = StandardMaterial::from(images.add(image));
+        material.unlit = true;
+        commands.spawn(PbrBundle {
+            mesh: plane_h.clone(),
+            material: materials.add(material),
+            transform: Transform::from_xyz(x, 0.0, 0.0),
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.0, 1.0, 12.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn create_test_image(size: u32, cx: f32, cy: f32) -> Image {
+    let data = (0..size * size)
+        .flat_map(|id| {
+            let mut x = 4.0 * (id % size) as f32 / (size - 1) as f32 - 2.0;
+            let mut y = 2.0 * (id / size) as f32 / (size - 1) as f32 - 1.0;
+            let mut count = 0;
+            while count < 0xFF && x * x + y * y < 4.0 {
+                let old_x = x;
+                x = x * x - y * y + cx;
+                y = 2.0 * old_x * y + cy;
+                count += 1;
+            }
+            [
+                0xFF - (count * 2) as u8,
+                0xFF - (count * 5) as u8,
+                0xFF - (count * 13) as u8,
+                u8::MAX,
+            ]
+        })
+        .collect();
+
+    Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                ..default()
+            },
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        data,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,294 @@
+//! Crate-native binary format for storing processed images so they can be loaded again without
+//! re-processing. Unlike KTX2/DDS this stores the bevy specific parts too (sampler, asset usage).
+//!
+//! Layout (little endian): magic, version, texture descriptor, sampler, asset usage, data.
+//! The texture label, view formats and texture view descriptor are not stored.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        texture::{
+            ImageAddressMode, ImageCompareFunction, ImageFilterMode, ImageSampler,
+            ImageSamplerBorderColor, ImageSamplerDescriptor,
+        },
+    },
+};
+
+use crate::MipmapError;
+
+const BLOB_MAGIC: &[u8; 4] = b"BMMG";
+const BLOB_VERSION: u32 = 1;
+
+/// Formats that can be stored in a blob. Formats are stored by index, only append to this list.
+const BLOB_FORMATS: &[TextureFormat] = &[
+    TextureFormat::R8Unorm,
+    TextureFormat::Rg8Unorm,
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::R16Unorm,
+    TextureFormat::Rg16Unorm,
+    TextureFormat::Rgba16Unorm,
+    TextureFormat::R16Float,
+    TextureFormat::Rg16Float,
+    TextureFormat::Rgba16Float,
+    TextureFormat::R32Float,
+    TextureFormat::Rg32Float,
+    TextureFormat::Rgba32Float,
+    TextureFormat::Bc1RgbaUnorm,
+    TextureFormat::Bc1RgbaUnormSrgb,
+    TextureFormat::Bc2RgbaUnorm,
+    TextureFormat::Bc2RgbaUnormSrgb,
+    TextureFormat::Bc3RgbaUnorm,
+    TextureFormat::Bc3RgbaUnormSrgb,
+    TextureFormat::Bc4RUnorm,
+    TextureFormat::Bc4RSnorm,
+    TextureFormat::Bc5RgUnorm,
+    TextureFormat::Bc5RgSnorm,
+    TextureFormat::Bc6hRgbUfloat,
+    TextureFormat::Bc6hRgbFloat,
+    TextureFormat::Bc7RgbaUnorm,
+    TextureFormat::Bc7RgbaUnormSrgb,
+];
+
+const DIMENSIONS: &[TextureDimension] = &[
+    TextureDimension::D1,
+    TextureDimension::D2,
+    TextureDimension::D3,
+];
+
+const ADDRESS_MODES: &[ImageAddressMode] = &[
+    ImageAddressMode::ClampToEdge,
+    ImageAddressMode::Repeat,
+    ImageAddressMode::MirrorRepeat,
+    ImageAddressMode::ClampToBorder,
+];
+
+const FILTER_MODES: &[ImageFilterMode] = &[ImageFilterMode::Nearest, ImageFilterMode::Linear];
+
+const COMPARE_FUNCTIONS: &[ImageCompareFunction] = &[
+    ImageCompareFunction::Never,
+    ImageCompareFunction::Less,
+    ImageCompareFunction::Equal,
+    ImageCompareFunction::LessEqual,
+    ImageCompareFunction::Greater,
+    ImageCompareFunction::NotEqual,
+    ImageCompareFunction::GreaterEqual,
+    ImageCompareFunction::Always,
+];
+
+const BORDER_COLORS: &[ImageSamplerBorderColor] = &[
+    ImageSamplerBorderColor::TransparentBlack,
+    ImageSamplerBorderColor::OpaqueBlack,
+    ImageSamplerBorderColor::OpaqueWhite,
+    ImageSamplerBorderColor::Zero,
+];
+
+/// Serializes a (processed) image into a compact binary blob, see `from_blob`.
+/// Errors if the image format isn't one of the formats supported by the blob format.
+pub fn to_blob(image: &Image) -> Result<Vec<u8>, MipmapError> {
+    let descriptor = &image.texture_descriptor;
+    let mut blob = Vec::with_capacity(image.data.len() + 128);
+    blob.extend_from_slice(BLOB_MAGIC);
+    write_u32(&mut blob, BLOB_VERSION);
+
+    write_u32(&mut blob, descriptor.size.width);
+    write_u32(&mut blob, descriptor.size.height);
+    write_u32(&mut blob, descriptor.size.depth_or_array_layers);
+    write_u32(&mut blob, descriptor.mip_level_count);
+    write_u32(&mut blob, descriptor.sample_count);
+    blob.push(index_of(DIMENSIONS, &descriptor.dimension)?);
+    write_u32(
+        &mut blob,
+        index_of(BLOB_FORMATS, &descriptor.format)? as u32,
+    );
+    write_u32(&mut blob, descriptor.usage.bits());
+
+    match &image.sampler {
+        ImageSampler::Default => blob.push(0),
+        ImageSampler::Descriptor(sampler) => {
+            blob.push(1);
+            write_sampler(&mut blob, sampler)?;
+        }
+    }
+
+    blob.push(image.asset_usage.bits());
+    write_u64(&mut blob, image.data.len() as u64);
+    blob.extend_from_slice(&image.data);
+    Ok(blob)
+}
+
+/// Reconstructs an image written with `to_blob`.
+pub fn from_blob(blob: &[u8]) -> Result<Image, MipmapError> {
+    let mut reader = BlobReader { blob, offset: 0 };
+    if reader.bytes(4)? != BLOB_MAGIC {
+        return Err(MipmapError::NotABlob);
+    }
+    let version = reader.u32()?;
+    if version != BLOB_VERSION {
+        return Err(MipmapError::UnsupportedBlobVersion {
+            version,
+            expected: BLOB_VERSION,
+        });
+    }
+
+    let mut image = Image::default();
+    let descriptor = &mut image.texture_descriptor;
+    descriptor.size = Extent3d {
+        width: reader.u32()?,
+        height: reader.u32()?,
+        depth_or_array_layers: reader.u32()?,
+    };
+    descriptor.mip_level_count = reader.u32()?;
+    descriptor.sample_count = reader.u32()?;
+    descriptor.dimension = reader.indexed(DIMENSIONS)?;
+    descriptor.format = reader.indexed_u32(BLOB_FORMATS)?;
+    descriptor.usage = TextureUsages::from_bits(reader.u32()?)
+        .ok_or(MipmapError::InvalidBlobField("texture usage"))?;
+    descriptor.view_formats = &[];
+
+    image.sampler = match reader.u8()? {
+        0 => ImageSampler::Default,
+        1 => ImageSampler::Descriptor(read_sampler(&mut reader)?),
+        _ => return Err(MipmapError::InvalidBlobField("sampler tag")),
+    };
+
+    image.asset_usage = RenderAssetUsages::from_bits(reader.u8()?)
+        .ok_or(MipmapError::InvalidBlobField("asset usage"))?;
+    let data_len = reader.u64()? as usize;
+    image.data = reader.bytes(data_len)?.to_vec();
+    Ok(image)
+}
+
+fn write_sampler(blob: &mut Vec<u8>, sampler: &ImageSamplerDescriptor) -> Result<(), MipmapError> {
+    let label = sampler.label.as_deref().unwrap_or_default().as_bytes();
+    write_u32(blob, label.len() as u32);
+    blob.extend_from_slice(label);
+    for address_mode in [
+        &sampler.address_mode_u,
+        &sampler.address_mode_v,
+        &sampler.address_mode_w,
+    ] {
+        blob.push(index_of(ADDRESS_MODES, address_mode)?);
+    }
+    for filter_mode in [
+        &sampler.mag_filter,
+        &sampler.min_filter,
+        &sampler.mipmap_filter,
+    ] {
+        blob.push(index_of(FILTER_MODES, filter_mode)?);
+    }
+    write_u32(blob, sampler.lod_min_clamp.to_bits());
+    write_u32(blob, sampler.lod_max_clamp.to_bits());
+    // Optional values are stored as index + 1, with 0 meaning None
+    blob.push(match &sampler.compare {
+        Some(compare) => index_of(COMPARE_FUNCTIONS, compare)? + 1,
+        None => 0,
+    });
+    blob.extend_from_slice(&sampler.anisotropy_clamp.to_le_bytes());
+    blob.push(match &sampler.border_color {
+        Some(border_color) => index_of(BORDER_COLORS, border_color)? + 1,
+        None => 0,
+    });
+    Ok(())
+}
+
+fn read_sampler(reader: &mut BlobReader) -> Result<ImageSamplerDescriptor, MipmapError> {
+    let label_len = reader.u32()? as usize;
+    let label = String::from_utf8(reader.bytes(label_len)?.to_vec())
+        .map_err(|_| MipmapError::InvalidBlobField("sampler label"))?;
+    Ok(ImageSamplerDescriptor {
+        label: (!label.is_empty()).then_some(label),
+        address_mode_u: reader.indexed(ADDRESS_MODES)?,
+        address_mode_v: reader.indexed(ADDRESS_MODES)?,
+        address_mode_w: reader.indexed(ADDRESS_MODES)?,
+        mag_filter: reader.indexed(FILTER_MODES)?,
+        min_filter: reader.indexed(FILTER_MODES)?,
+        mipmap_filter: reader.indexed(FILTER_MODES)?,
+        lod_min_clamp: f32::from_bits(reader.u32()?),
+        lod_max_clamp: f32::from_bits(reader.u32()?),
+        compare: reader.optional_indexed(COMPARE_FUNCTIONS)?,
+        anisotropy_clamp: u16::from_le_bytes([reader.u8()?, reader.u8()?]),
+        border_color: reader.optional_indexed(BORDER_COLORS)?,
+    })
+}
+
+/// Only used with fieldless enums (and fieldless `TextureFormat` variants), so comparing
+/// discriminants is enough. Not all of bevy's sampler enums implement `PartialEq`.
+fn index_of<T: std::fmt::Debug>(values: &[T], value: &T) -> Result<u8, MipmapError> {
+    values
+        .iter()
+        .position(|v| std::mem::discriminant(v) == std::mem::discriminant(value))
+        .map(|i| i as u8)
+        .ok_or_else(|| MipmapError::UnsupportedInBlob(format!("{value:?}")))
+}
+
+fn write_u32(blob: &mut Vec<u8>, value: u32) {
+    blob.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(blob: &mut Vec<u8>, value: u64) {
+    blob.extend_from_slice(&value.to_le_bytes());
+}
+
+struct BlobReader<'a> {
+    blob: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BlobReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MipmapError> {
+        let bytes = self
+            .blob
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or(MipmapError::TruncatedBlob)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MipmapError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, MipmapError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, MipmapError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn indexed<T: Clone>(&mut self, values: &[T]) -> Result<T, MipmapError> {
+        let index = self.u8()? as usize;
+        values
+            .get(index)
+            .cloned()
+            .ok_or(MipmapError::InvalidBlobField("index"))
+    }
+
+    fn indexed_u32<T: Clone>(&mut self, values: &[T]) -> Result<T, MipmapError> {
+        let index = self.u32()? as usize;
+        values
+            .get(index)
+            .cloned()
+            .ok_or(MipmapError::InvalidBlobField("index"))
+    }
+
+    fn optional_indexed<T: Clone>(&mut self, values: &[T]) -> Result<Option<T>, MipmapError> {
+        match self.u8()? as usize {
+            0 => Ok(None),
+            index => values
+                .get(index - 1)
+                .cloned()
+                .map(Some)
+                .ok_or(MipmapError::InvalidBlobField("index")),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Color grading baked into color textures before their mips are generated, see
+//! `MipmapGeneratorSettings::color_grade`.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+};
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{TextureDimension, TextureFormat},
+};
+use image::DynamicImage;
+
+use crate::MipmapError;
+
+/// A color transform applied to mip 0 of color textures, so the base level and all mips reflect
+/// the grade without a runtime shader pass. Colors are passed as stored (0-1, sRGB encoded for
+/// sRGB textures) and alpha is left unchanged.
+#[derive(Clone)]
+pub struct ColorGrade {
+    // Only used for cache hashes
+    #[cfg_attr(not(feature = "compress"), allow(dead_code))]
+    key: u64,
+    grade: Arc<dyn Fn([f32; 3]) -> [f32; 3] + Send + Sync>,
+}
+
+impl ColorGrade {
+    /// `key` identifies the grade in the compressed data caches, change it whenever `grade`
+    /// changes or cached textures will keep the old grade.
+    pub fn from_fn(key: u64, grade: impl Fn([f32; 3]) -> [f32; 3] + Send + Sync + 'static) -> Self {
+        ColorGrade {
+            key,
+            grade: Arc::new(grade),
+        }
+    }
+
+    /// Builds a grade from a 3D color lookup table, sampled with trilinear interpolation.
+    /// `lut` is an Rgba8 image that's either 3D (n x n x n) or a 2D strip (n * n x n) where
+    /// blue selects the n x n slice, red is x within the slice and green is y. Its values are
+    /// used as stored, so the LUT should be authored for the encoding of the textures it's
+    /// applied to.
+    pub fn from_lut(lut: &Image) -> Result<Self, MipmapError> {
+        let descriptor = &lut.texture_descriptor;
+        if !matches!(
+            descriptor.format,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+        ) {
+            return Err(MipmapError::UnsupportedLutFormat(descriptor.format));
+        }
+        let size = descriptor.size;
+        let n = size.height as usize;
+        let is_3d = descriptor.dimension == TextureDimension::D3
+            && size.width as usize == n
+            && size.depth_or_array_layers as usize == n;
+        let is_strip = descriptor.dimension == TextureDimension::D2
+            && size.width as usize == n * n
+            && size.depth_or_array_layers == 1;
+        if n < 2 || !(is_3d || is_strip) {
+            return Err(MipmapError::InvalidLutSize(size));
+        }
+        if lut.data.len() < n * n * n * 4 {
+            return Err(MipmapError::LutDataTooShort {
+                len: lut.data.len(),
+                needed: n * n * n * 4,
+            });
+        }
+
+        let table: Vec<[f32; 3]> = (0..n * n * n)
+            .map(|i| {
+                let (r, g, b) = (i % n, (i / n) % n, i / (n * n));
+                let pixel = if is_3d {
+                    (b * n + g) * n + r
+                } else {
+                    g * n * n + b * n + r
+                };
+                let texel = &lut.data[pixel * 4..pixel * 4 + 3];
+                [texel[0], texel[1], texel[2]].map(|c| c as f32 / 255.0)
+            })
+            .collect();
+
+        let mut hasher = DefaultHasher::new();
+        n.hash(&mut hasher);
+        for entry in &table {
+            entry.map(f32::to_bits).hash(&mut hasher);
+        }
+
+        Ok(ColorGrade::from_fn(hasher.finish(), move |color| {
+            sample_lut(&table, n, color)
+        }))
+    }
+
+    #[cfg(feature = "compress")]
+    pub(crate) fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub(crate) fn apply(&self, dyn_image: &mut DynamicImage) {
+        match dyn_image {
+            DynamicImage::ImageRgba8(image) => {
+                for pixel in image.pixels_mut() {
+                    let color = (self.grade)([0, 1, 2].map(|c| pixel.0[c] as f32 / 255.0));
+                    for (channel, value) in pixel.0.iter_mut().zip(color) {
+                        *channel = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+            DynamicImage::ImageRgba16(image) => {
+                for pixel in image.pixels_mut() {
+                    let color = (self.grade)([0, 1, 2].map(|c| pixel.0[c] as f32 / 65535.0));
+                    for (channel, value) in pixel.0.iter_mut().zip(color) {
+                        *channel = (value * 65535.0).round().clamp(0.0, 65535.0) as u16;
+                    }
+                }
+            }
+            DynamicImage::ImageRgba32F(image) => {
+                for pixel in image.pixels_mut() {
+                    let color = (self.grade)([pixel.0[0], pixel.0[1], pixel.0[2]]);
+                    pixel.0[..3].copy_from_slice(&color);
+                }
+            }
+            dyn_image => warn!(
+                "Color grading is not supported for {:?}, skipping.",
+                dyn_image.color()
+            ),
+        }
+    }
+}
+
+fn sample_lut(table: &[[f32; 3]], n: usize, color: [f32; 3]) -> [f32; 3] {
+    let coords = color.map(|c| c.clamp(0.0, 1.0) * (n - 1) as f32);
+    let base = coords.map(|c| (c as usize).min(n - 2));
+    let mut result = [0.0; 3];
+    for corner in 0..8 {
+        let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let mut weight = 1.0;
+        for axis in 0..3 {
+            let t = coords[axis] - base[axis] as f32;
+            weight *= if offset[axis] == 1 { t } else { 1.0 - t };
+        }
+        let entry = table
+            [(base[0] + offset[0]) + (base[1] + offset[1]) * n + (base[2] + offset[2]) * n * n];
+        for c in 0..3 {
+            result[c] += entry[c] * weight;
+        }
+    }
+    result
+}
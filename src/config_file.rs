@@ -0,0 +1,211 @@
+//! Loading `MipmapGeneratorSettings` from a RON file and reloading them when it changes, for
+//! tuning settings without recompiling. Enabled with the `config_file` feature.
+
+use std::{path::PathBuf, time::Duration, time::SystemTime};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    AnisotropicFiltering, AnisotropyPolicy, CompressionFormat, CompressionSpeed, FilterTypeName,
+    GammaMode, MipmapGeneratorSettings, ProcessingOrder,
+};
+
+/// Applies the settings in a RON file to `MipmapGeneratorSettings`, and applies them again
+/// whenever the file is modified. Add it after `MipmapGeneratorPlugin`.
+///
+/// Every field is optional, fields that are left out keep the value the app started with:
+/// ```ron
+/// (
+///     anisotropic_filtering: Some(X16),
+///     filter_type: Some(Lanczos3),
+///     minimum_mip_resolution: Some(16),
+///     post_downsample_sharpen: Some(0.5),
+///     compression: Some(Fast),
+///     low_quality: Some(false),
+/// )
+/// ```
+/// Also supported: `enabled`, `anisotropy_policy`, `anisotropy_min_resolution`, `base_scale`,
+/// `alpha_coverage_cutoff`, `renormalize_normals`, `progressive`, `linear_color_downsample`,
+/// `downsample_gamma`, `high_precision`, `compression_format`, `use_bc3_for_rgba`,
+/// `compress_only`, `cubemap_seamless`, `detect_opaque_alpha`, `detect_grayscale`,
+/// `max_new_tasks_per_frame`, `apply_budget_per_frame`, `processing_order`,
+/// `release_unused_mips`, `keep_source_images`, `strict` and `strict_panic`.
+///
+/// New settings only apply to images processed after the change, images that already have mips
+/// keep them.
+pub struct MipmapSettingsFilePlugin {
+    pub path: PathBuf,
+    /// How often the modification time of the file is checked.
+    pub poll_interval: Duration,
+}
+
+impl MipmapSettingsFilePlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        MipmapSettingsFilePlugin {
+            path: path.into(),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Plugin for MipmapSettingsFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SettingsFileWatch {
+            path: self.path.clone(),
+            timer: Timer::new(self.poll_interval, TimerMode::Repeating),
+            last_modified: None,
+            base: None,
+        })
+        .add_systems(PreUpdate, reload_settings_file);
+    }
+}
+
+#[derive(Resource)]
+struct SettingsFileWatch {
+    path: PathBuf,
+    timer: Timer,
+    last_modified: Option<SystemTime>,
+    /// The settings from before the file was first applied, fields missing from the file use these.
+    base: Option<MipmapGeneratorSettings>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MipmapSettingsFile {
+    enabled: Option<bool>,
+    anisotropic_filtering: Option<AnisotropicFiltering>,
+    anisotropy_policy: Option<AnisotropyPolicy>,
+    anisotropy_min_resolution: Option<u32>,
+    filter_type: Option<FilterTypeName>,
+    minimum_mip_resolution: Option<u32>,
+    base_scale: Option<u32>,
+    post_downsample_sharpen: Option<f32>,
+    alpha_coverage_cutoff: Option<f32>,
+    renormalize_normals: Option<bool>,
+    progressive: Option<bool>,
+    linear_color_downsample: Option<bool>,
+    downsample_gamma: Option<GammaMode>,
+    high_precision: Option<bool>,
+    compression: Option<CompressionSpeed>,
+    compression_format: Option<CompressionFormat>,
+    low_quality: Option<bool>,
+    use_bc3_for_rgba: Option<bool>,
+    compress_only: Option<bool>,
+    cubemap_seamless: Option<bool>,
+    detect_opaque_alpha: Option<bool>,
+    detect_grayscale: Option<bool>,
+    max_new_tasks_per_frame: Option<usize>,
+    apply_budget_per_frame: Option<usize>,
+    processing_order: Option<ProcessingOrder>,
+    release_unused_mips: Option<bool>,
+    keep_source_images: Option<bool>,
+    strict: Option<bool>,
+    strict_panic: Option<bool>,
+}
+
+impl MipmapSettingsFile {
+    fn apply(&self, settings: &mut MipmapGeneratorSettings) {
+        fn set<T: Copy>(value: Option<T>, field: &mut T) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        set(self.enabled, &mut settings.enabled);
+        set(
+            self.anisotropic_filtering,
+            &mut settings.anisotropic_filtering,
+        );
+        set(self.anisotropy_policy, &mut settings.anisotropy_policy);
+        set(
+            self.anisotropy_min_resolution,
+            &mut settings.anisotropy_min_resolution,
+        );
+        set(self.filter_type.map(Into::into), &mut settings.filter_type);
+        set(
+            self.minimum_mip_resolution,
+            &mut settings.minimum_mip_resolution,
+        );
+        set(self.base_scale, &mut settings.base_scale);
+        if self.post_downsample_sharpen.is_some() {
+            settings.post_downsample_sharpen = self.post_downsample_sharpen;
+        }
+        if self.alpha_coverage_cutoff.is_some() {
+            settings.alpha_coverage_cutoff = self.alpha_coverage_cutoff;
+        }
+        set(self.renormalize_normals, &mut settings.renormalize_normals);
+        set(self.progressive, &mut settings.progressive);
+        set(
+            self.linear_color_downsample,
+            &mut settings.linear_color_downsample,
+        );
+        set(self.downsample_gamma, &mut settings.downsample_gamma);
+        set(self.high_precision, &mut settings.high_precision);
+        if self.compression.is_some() {
+            settings.compression = self.compression;
+        }
+        set(self.compression_format, &mut settings.compression_format);
+        set(self.low_quality, &mut settings.low_quality);
+        set(self.use_bc3_for_rgba, &mut settings.use_bc3_for_rgba);
+        set(self.compress_only, &mut settings.compress_only);
+        set(self.cubemap_seamless, &mut settings.cubemap_seamless);
+        set(self.detect_opaque_alpha, &mut settings.detect_opaque_alpha);
+        set(self.detect_grayscale, &mut settings.detect_grayscale);
+        if self.max_new_tasks_per_frame.is_some() {
+            settings.max_new_tasks_per_frame = self.max_new_tasks_per_frame;
+        }
+        if self.apply_budget_per_frame.is_some() {
+            settings.apply_budget_per_frame = self.apply_budget_per_frame;
+        }
+        set(self.processing_order, &mut settings.processing_order);
+        set(self.release_unused_mips, &mut settings.release_unused_mips);
+        set(self.keep_source_images, &mut settings.keep_source_images);
+        set(self.strict, &mut settings.strict);
+        set(self.strict_panic, &mut settings.strict_panic);
+    }
+}
+
+fn reload_settings_file(
+    mut watch: ResMut<SettingsFileWatch>,
+    time: Res<Time>,
+    mut settings: ResMut<MipmapGeneratorSettings>,
+) {
+    // Always check on the first run so the file applies from the start
+    let first_run = watch.base.is_none();
+    if !watch.timer.tick(time.delta()).just_finished() && !first_run {
+        return;
+    }
+    let base = watch.base.get_or_insert_with(|| settings.clone()).clone();
+
+    let modified = match std::fs::metadata(&watch.path).and_then(|m| m.modified()) {
+        Ok(modified) => modified,
+        Err(e) => {
+            if first_run {
+                warn!("Can't read mipmap settings file {:?}: {}", watch.path, e);
+            }
+            return;
+        }
+    };
+    if watch.last_modified == Some(modified) {
+        return;
+    }
+    watch.last_modified = Some(modified);
+
+    let file = match std::fs::read_to_string(&watch.path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str::<MipmapSettingsFile>(&text).map_err(|e| e.to_string()))
+    {
+        Ok(file) => file,
+        Err(e) => {
+            warn!(
+                "Failed to load mipmap settings file {:?}: {}",
+                watch.path, e
+            );
+            return;
+        }
+    };
+    let mut new_settings = base;
+    file.apply(&mut new_settings);
+    *settings = new_settings;
+    info!("Loaded mipmap settings from {:?}", watch.path);
+}
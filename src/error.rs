@@ -0,0 +1,195 @@
+//! Error type of the public functions.
+
+use std::fmt;
+
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::ColorSpace;
+
+/// Reasons an image can't be processed, returned by the public functions of this crate.
+#[derive(Debug)]
+pub enum MipmapError {
+    /// Already compressed images can't be processed.
+    CompressedInput,
+    /// Only `TextureDimension::D2` images can be processed. wgpu doesn't allow mips on `D1`
+    /// textures, a `D2` texture with a height of 1 can be used instead.
+    UnsupportedDimension(TextureDimension),
+    /// The image is a cubemap, which needs `MipmapGeneratorSettings::cubemap_seamless`.
+    CubemapNotEnabled,
+    /// Extracting mip levels is only supported for single layer images.
+    UnsupportedLayerCount(u32),
+    /// The format can't be converted into a `DynamicImage`.
+    UnsupportedFormat(TextureFormat),
+    /// The image data doesn't match the size and format of the image.
+    InvalidData(TextureFormat),
+    /// The `DynamicImage` variant has no BCn equivalent.
+    UnsupportedColorType(image::ColorType),
+    /// BCn compression needs images of at least 4x4 pixels.
+    TooSmallForCompression,
+    /// The format has no variant in the requested color space.
+    NoColorSpaceVariant {
+        format: TextureFormat,
+        color_space: ColorSpace,
+    },
+    /// The format has no block copy size, so its mip levels can't be located in the data.
+    NoBlockCopySize(TextureFormat),
+    /// A mip level outside of the chain was requested. `extract_mip_level` counts levels from 1.
+    MipLevelOutOfRange { mip_level: u32, available: u32 },
+    /// Mip levels `start..end` were requested, but the chain ends earlier.
+    MipRangeOutOfRange {
+        start: u32,
+        end: u32,
+        available: u32,
+    },
+    /// The image data is shorter than the requested mip level needs.
+    DataTooShort {
+        len: usize,
+        needed: usize,
+        mip_level: u32,
+    },
+    /// `compressed_from_level` of `generate_mips_texture_split` isn't a level after the first one.
+    SplitLevelOutOfRange { level: u32, mip_level_count: u32 },
+    /// `MipmapGeneratorSettings::force_output_format` needs the compress feature for BCn formats.
+    CompressFeatureDisabled(TextureFormat),
+    /// The source can't be stored in `MipmapGeneratorSettings::force_output_format` without
+    /// converting channels.
+    ForcedFormatIncompatible {
+        source: TextureFormat,
+        forced: TextureFormat,
+    },
+    /// The layers of a 2D array or cubemap were processed into different formats or level counts.
+    ArrayLayoutMismatch,
+    /// The color LUT isn't an Rgba8 image.
+    UnsupportedLutFormat(TextureFormat),
+    /// The color LUT isn't n x n x n (3D) or n * n x n (2D strip).
+    InvalidLutSize(Extent3d),
+    /// The color LUT data is shorter than its size needs.
+    LutDataTooShort { len: usize, needed: usize },
+    /// The data doesn't start with the magic bytes of an image blob.
+    NotABlob,
+    /// The blob was written by an incompatible version of this crate.
+    UnsupportedBlobVersion { version: u32, expected: u32 },
+    /// The blob ends before all of its fields were read.
+    TruncatedBlob,
+    /// A field of the blob has an invalid value, named by the string.
+    InvalidBlobField(&'static str),
+    /// The image has a value that can't be stored in a blob, like an unsupported format.
+    UnsupportedInBlob(String),
+    /// The glTF material extras aren't valid JSON or don't match the supported fields.
+    InvalidGltfExtras(String),
+}
+
+impl fmt::Display for MipmapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MipmapError::CompressedInput => write!(f, "Compressed images not supported"),
+            MipmapError::UnsupportedDimension(TextureDimension::D1) => write!(
+                f,
+                "1D textures can't have mip levels, use a TextureDimension::D2 image with a \
+                height of 1 instead."
+            ),
+            MipmapError::UnsupportedDimension(dimension) => write!(
+                f,
+                "Image has dimension {dimension:?} but only TextureDimension::D2 is supported."
+            ),
+            MipmapError::CubemapNotEnabled => write!(
+                f,
+                "Image is a cubemap, set MipmapGeneratorSettings::cubemap_seamless to generate its \
+                mips."
+            ),
+            MipmapError::UnsupportedLayerCount(layers) => write!(
+                f,
+                "Image contains {layers} layers only a single layer is supported."
+            ),
+            MipmapError::UnsupportedFormat(format) => write!(
+                f,
+                "Conversion into dynamic image not supported for {format:?}."
+            ),
+            MipmapError::InvalidData(format) => write!(f, "Failed to convert into {format:?}."),
+            MipmapError::UnsupportedColorType(color_type) => write!(
+                f,
+                "Conversion into dynamic image not supported for {color_type:?}."
+            ),
+            MipmapError::TooSmallForCompression => {
+                write!(f, "Image size too small for BCn compression")
+            }
+            MipmapError::NoColorSpaceVariant {
+                format,
+                color_space,
+            } => write!(f, "{format:?} has no {color_space:?} variant."),
+            MipmapError::NoBlockCopySize(format) => write!(
+                f,
+                "{format:?} has no block copy size without a texture aspect, so its mip levels \
+                can't be extracted."
+            ),
+            MipmapError::MipLevelOutOfRange {
+                mip_level,
+                available,
+            } => write!(
+                f,
+                "Mip level {mip_level} requested, but only levels 1..={available} are avaliable."
+            ),
+            MipmapError::MipRangeOutOfRange {
+                start,
+                end,
+                available,
+            } => write!(
+                f,
+                "Mip levels {start}..{end} requested, but only {available} are avaliable."
+            ),
+            MipmapError::DataTooShort {
+                len,
+                needed,
+                mip_level,
+            } => write!(
+                f,
+                "Image data is {len} bytes, but {needed} are needed for mip level {mip_level}."
+            ),
+            MipmapError::SplitLevelOutOfRange {
+                level,
+                mip_level_count,
+            } => write!(
+                f,
+                "compressed_from_level must be between 1 and {}, got {level}.",
+                mip_level_count.saturating_sub(1)
+            ),
+            MipmapError::CompressFeatureDisabled(format) => write!(
+                f,
+                "Forcing the output format to {format:?} requires the compress feature."
+            ),
+            MipmapError::ForcedFormatIncompatible { source, forced } => write!(
+                f,
+                "{source:?} images can't be stored as the forced output format {forced:?}."
+            ),
+            MipmapError::ArrayLayoutMismatch => write!(
+                f,
+                "The layers of the array were processed into different formats."
+            ),
+            MipmapError::UnsupportedLutFormat(format) => write!(
+                f,
+                "Color LUT format {format:?} is not supported, expected Rgba8."
+            ),
+            MipmapError::InvalidLutSize(size) => write!(
+                f,
+                "Color LUT size {}x{}x{} is not n x n x n (3D) or n * n x n (2D strip).",
+                size.width, size.height, size.depth_or_array_layers
+            ),
+            MipmapError::LutDataTooShort { len, needed } => {
+                write!(f, "Color LUT data is {len} bytes, expected {needed}.")
+            }
+            MipmapError::NotABlob => write!(f, "Not a mipmap generator image blob."),
+            MipmapError::UnsupportedBlobVersion { version, expected } => write!(
+                f,
+                "Unsupported image blob version {version}, expected {expected}."
+            ),
+            MipmapError::TruncatedBlob => write!(f, "Image blob is truncated."),
+            MipmapError::InvalidBlobField(field) => write!(f, "Invalid {field} in image blob."),
+            MipmapError::UnsupportedInBlob(value) => {
+                write!(f, "{value} is not supported in image blobs.")
+            }
+            MipmapError::InvalidGltfExtras(error) => write!(f, "Invalid material extras: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for MipmapError {}
@@ -0,0 +1,100 @@
+//! Per material settings from glTF material extras, so artists can control mipmapping per asset.
+//! Enabled with the `gltf_extras` feature.
+
+use bevy::{gltf::GltfMaterialExtras, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    CompressionSpeed, FilterTypeName, MipmapError, MipmapGeneratorSettings, MipmapSettingsOverride,
+    NoMipmapGeneration,
+};
+
+/// The `mipmap` object in a material's extras. Fields that are left out keep the base settings.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MaterialExtrasSettings {
+    enabled: Option<bool>,
+    filter_type: Option<FilterTypeName>,
+    minimum_mip_resolution: Option<u32>,
+    compression: Option<bool>,
+    compression_speed: Option<CompressionSpeed>,
+    low_quality: Option<bool>,
+}
+
+impl MaterialExtrasSettings {
+    fn to_override(&self) -> MipmapSettingsOverride {
+        MipmapSettingsOverride {
+            compression: self.compression,
+            compression_speed: self.compression_speed,
+            low_quality: self.low_quality,
+            filter_type: self.filter_type.map(Into::into),
+            minimum_mip_resolution: self.minimum_mip_resolution,
+            ..default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MaterialExtras {
+    mipmap: Option<MaterialExtrasSettings>,
+}
+
+/// Parses the `mipmap` object of a material's extras JSON, `None` if there is none.
+fn parse_gltf_extras(json: &str) -> Result<Option<MaterialExtrasSettings>, MipmapError> {
+    let extras: MaterialExtras =
+        serde_json::from_str(json).map_err(|e| MipmapError::InvalidGltfExtras(e.to_string()))?;
+    Ok(extras.mipmap)
+}
+
+/// Reads the `mipmap` object of a glTF material's extras JSON and applies it over `base`.
+/// Returns `None` if the extras have no `mipmap` object. For example:
+/// ```json
+/// { "mipmap": { "filter_type": "Lanczos3", "compression": false } }
+/// ```
+/// Supported fields are `enabled`, `filter_type`, `minimum_mip_resolution`, `compression`
+/// (on or off), `compression_speed` and `low_quality`. Compression only applies with the
+/// `compress` feature.
+pub fn settings_from_gltf_extras(
+    json: &str,
+    base: &MipmapGeneratorSettings,
+) -> Result<Option<MipmapGeneratorSettings>, MipmapError> {
+    let Some(extras) = parse_gltf_extras(json)? else {
+        return Ok(None);
+    };
+    let mut settings = extras.to_override().apply_to(base);
+    if let Some(enabled) = extras.enabled {
+        settings.enabled = enabled;
+    }
+    Ok(Some(settings))
+}
+
+/// Adds a `MipmapSettingsOverride` to glTF mesh entities whose material extras have a `mipmap`
+/// object, see `settings_from_gltf_extras`. Fields the extras leave out follow the global settings.
+/// Entities with `"enabled": false` get `NoMipmapGeneration` instead.
+///
+/// glTF materials finish loading in the same frame the scene is spawned, so the override has to
+/// be added before `generate_mipmaps` reads the material events. Schedule both after the scene
+/// spawner, for example:
+/// ```ignore
+/// app.add_systems(
+///     PostUpdate,
+///     (apply_gltf_material_extras, generate_mipmaps::<StandardMaterial>).chain(),
+/// );
+/// ```
+pub fn apply_gltf_material_extras(
+    mut commands: Commands,
+    extras: Query<(Entity, &GltfMaterialExtras), Added<GltfMaterialExtras>>,
+) {
+    for (entity, material_extras) in &extras {
+        match parse_gltf_extras(&material_extras.value) {
+            Ok(Some(extras)) if extras.enabled == Some(false) => {
+                commands.entity(entity).insert(NoMipmapGeneration);
+            }
+            Ok(Some(extras)) => {
+                commands.entity(entity).insert(extras.to_override());
+            }
+            Ok(None) => (),
+            Err(e) => warn!("Ignoring mipmap settings of {:?}: {}", entity, e),
+        }
+    }
+}
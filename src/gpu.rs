@@ -0,0 +1,314 @@
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    prelude::*,
+    render::{
+        render_asset::{prepare_assets, RenderAssets},
+        render_resource::{
+            binding_types::{sampler, texture_2d},
+            *,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+        Extract, Render, RenderApp, RenderSet,
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    calculate_mip_count, check_image_compatible, mip_chain_byte_size, sampler_max_mip_count,
+    MipmapGeneratorSettings, MipmapImageOverrides,
+};
+
+const GPU_DOWNSAMPLE_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(148526083548120927093261597240934712);
+
+/// Generates mip chains on the GPU for images listed in [`GpuMipmapImages`].
+/// Requires `MipmapGeneratorPlugin` and the render plugins from `DefaultPlugins`.
+pub struct GpuMipmapGeneratorPlugin;
+impl Plugin for GpuMipmapGeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            GPU_DOWNSAMPLE_SHADER_HANDLE,
+            "gpu_downsample.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.init_resource::<GpuMipmapImages>()
+            .init_resource::<GpuMipmapChains>()
+            .add_systems(PostUpdate, prepare_gpu_mipmaps);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<GpuMipmapTextures>()
+            .init_resource::<SpecializedRenderPipelines<GpuDownsamplePipeline>>()
+            .add_systems(ExtractSchedule, extract_gpu_mipmap_chains)
+            .add_systems(
+                Render,
+                downsample_gpu_mipmaps
+                    .in_set(RenderSet::PrepareResources)
+                    .after(prepare_assets::<GpuImage>),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<GpuDownsamplePipeline>();
+    }
+}
+
+/// Images in this set skip the CPU path in `generate_mipmaps` and have their mip chain generated
+/// on the GPU instead, avoiding the CPU downsampling cost for runtime textures.
+/// Compression is not applied to these images since that still requires the CPU.
+///
+/// Only uncompressed formats that can be rendered to are supported (R8Unorm, Rg8Unorm,
+/// Rgba8Unorm, Rgba8UnormSrgb). Mip 0 is re-uploaded once together with the space for the rest of
+/// the chain, so the image needs to keep its main world data (`RenderAssetUsages::MAIN_WORLD`).
+/// The lower levels only hold zeros on the CPU, so they are generated again every time the image
+/// is uploaded, for example after it's modified with `Assets::get_mut`.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct GpuMipmapImages(pub HashSet<AssetId<Image>>);
+
+/// Images whose chain was allocated by `prepare_gpu_mipmaps`, so only their lower levels are
+/// overwritten on the GPU. Images that already had mips keep them.
+#[derive(Resource, Default)]
+struct GpuMipmapChains(HashSet<AssetId<Image>>);
+
+/// Render world copy of `GpuMipmapChains`, with the texture each chain was last generated for.
+/// Re-extracting an image creates a new texture, which has its lower levels generated again.
+#[derive(Resource, Default)]
+struct GpuMipmapTextures {
+    chains: HashSet<AssetId<Image>>,
+    generated: HashMap<AssetId<Image>, TextureId>,
+}
+
+fn is_gpu_downsample_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::R8Unorm
+            | TextureFormat::Rg8Unorm
+            | TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+    )
+}
+
+/// Allocates the full mip chain for flagged images so the texture that gets uploaded already has
+/// every level. Only mip 0 contains real data, the lower levels are filled in on the GPU.
+fn prepare_gpu_mipmaps(
+    gpu_images: Res<GpuMipmapImages>,
+    mut chains: ResMut<GpuMipmapChains>,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<MipmapGeneratorSettings>,
+    image_overrides: Option<Res<MipmapImageOverrides>>,
+) {
+    chains
+        .0
+        .retain(|id| gpu_images.contains(id) && images.contains(*id));
+    for id in gpu_images.iter() {
+        // Check with get() first so unchanged images don't trigger change detection.
+        let Some(image) = images.get(*id) else {
+            continue;
+        };
+        if image.texture_descriptor.mip_level_count != 1 {
+            continue;
+        }
+        if let Err(e) = check_image_compatible(image, &settings) {
+            warn!("Skipping GPU mipmap generation: {}", e);
+            continue;
+        }
+        if image.texture_descriptor.size.depth_or_array_layers != 1 {
+            warn!("GPU mipmap generation not supported for texture arrays.");
+            continue;
+        }
+        if !is_gpu_downsample_format(image.texture_descriptor.format) {
+            warn!(
+                "GPU mipmap generation not supported for {:?}.",
+                image.texture_descriptor.format
+            );
+            continue;
+        }
+        let Some(image) = images.get_mut(*id) else {
+            continue;
+        };
+        let descriptor = &mut image.texture_descriptor;
+        let mip_count = calculate_mip_count(
+            descriptor.size.width,
+            descriptor.size.height,
+            image_overrides
+                .as_ref()
+                .and_then(|overrides| overrides.get(id)?.minimum_mip_resolution)
+                .unwrap_or(settings.minimum_mip_resolution),
+            sampler_max_mip_count(&image.sampler),
+            None,
+        );
+        let chain_size = mip_chain_byte_size(
+            descriptor.size.width,
+            descriptor.size.height,
+            descriptor.format,
+            mip_count,
+        );
+        image.data.resize(chain_size, 0);
+        descriptor.mip_level_count = mip_count;
+        descriptor.usage |= TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+        chains.0.insert(*id);
+    }
+}
+
+fn extract_gpu_mipmap_chains(
+    mut textures: ResMut<GpuMipmapTextures>,
+    chains: Extract<Res<GpuMipmapChains>>,
+) {
+    let GpuMipmapTextures {
+        chains: extracted,
+        generated,
+    } = &mut *textures;
+    extracted.clone_from(&chains.0);
+    generated.retain(|id, _| extracted.contains(id));
+}
+
+#[derive(Resource)]
+struct GpuDownsamplePipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+}
+
+impl FromWorld for GpuDownsamplePipeline {
+    fn from_world(render_world: &mut World) -> Self {
+        let render_device = render_world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "mipmap_generator_downsample_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("mipmap_generator_downsample_sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+
+        GpuDownsamplePipeline { layout, sampler }
+    }
+}
+
+impl SpecializedRenderPipeline for GpuDownsamplePipeline {
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("mipmap_generator_downsample_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: GPU_DOWNSAMPLE_SHADER_HANDLE,
+                shader_defs: vec![],
+                entry_point: "fs_main".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: Vec::new(),
+        }
+    }
+}
+
+/// Renders each mip level from the previous one for every chain whose texture hasn't been
+/// generated yet. Images are skipped until their `GpuImage` with the full chain exists and the
+/// pipeline is compiled.
+fn downsample_gpu_mipmaps(
+    mut textures: ResMut<GpuMipmapTextures>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    pipeline: Res<GpuDownsamplePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<GpuDownsamplePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if textures.chains.is_empty() {
+        return;
+    }
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("mipmap_generator_downsample"),
+    });
+    let mut any_processed = false;
+
+    let GpuMipmapTextures { chains, generated } = &mut *textures;
+    for id in chains.iter() {
+        let Some(gpu_image) = gpu_images.get(*id) else {
+            continue;
+        };
+        if gpu_image.mip_level_count == 1 {
+            // Still the previous upload, wait for the one with the full chain.
+            continue;
+        }
+        if generated.get(id) == Some(&gpu_image.texture.id()) {
+            continue;
+        }
+        let pipeline_id =
+            pipelines.specialize(&pipeline_cache, &pipeline, gpu_image.texture_format);
+        let Some(render_pipeline) = pipeline_cache.get_render_pipeline(pipeline_id) else {
+            continue;
+        };
+
+        let level_view = |level: u32| {
+            gpu_image.texture.create_view(&TextureViewDescriptor {
+                label: Some("mipmap_generator_downsample_view"),
+                dimension: Some(TextureViewDimension::D2),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..default()
+            })
+        };
+
+        for level in 1..gpu_image.mip_level_count {
+            let source = level_view(level - 1);
+            let target = level_view(level);
+            let bind_group = render_device.create_bind_group(
+                "mipmap_generator_downsample_bind_group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((&source, &pipeline.sampler)),
+            );
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("mipmap_generator_downsample_pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(default()),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(render_pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        generated.insert(*id, gpu_image.texture.id());
+        any_processed = true;
+    }
+
+    if any_processed {
+        render_queue.submit([encoder.finish()]);
+    }
+}
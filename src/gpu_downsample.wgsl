@@ -0,0 +1,11 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var in_texture: texture_2d<f32>;
+@group(0) @binding(1) var in_sampler: sampler;
+
+// Sampling the previous level at the center of a texel of the next level lands between four
+// source texels, so bilinear filtering gives a 2x2 box downsample in a single fetch.
+@fragment
+fn fs_main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(in_texture, in_sampler, in.uv, 0.0);
+}
@@ -6,31 +6,154 @@ use std::{
     path::Path,
 };
 
+use std::{
+    marker::PhantomData,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+#[cfg(feature = "compress")]
 use anyhow::anyhow;
 
 use bevy::{
+    asset::UntypedAssetId,
+    ecs::system::SystemParam,
     pbr::{ExtendedMaterial, MaterialExtension},
     prelude::*,
     render::{
         render_asset::RenderAssetUsages,
-        render_resource::{Extent3d, TextureDimension, TextureFormat},
-        texture::{ImageSampler, ImageSamplerDescriptor},
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureViewDimension,
+        },
+        texture::{
+            ImageAddressMode, ImageCompareFunction, ImageFilterMode, ImageSampler,
+            ImageSamplerBorderColor, ImageSamplerDescriptor,
+        },
     },
-    tasks::{AsyncComputeTaskPool, Task},
-    utils::HashMap,
+    tasks::{AsyncComputeTaskPool, Task, TaskPool},
+    utils::{HashMap, HashSet, Instant},
 };
 use futures_lite::future;
-use image::{imageops::FilterType, DynamicImage, ImageBuffer};
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, Rgba};
+
+mod blob;
+mod color_grade;
+#[cfg(feature = "config_file")]
+mod config_file;
+mod error;
+#[cfg(feature = "gltf_extras")]
+mod gltf_extras;
+mod gpu;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "gpu_validation")]
+mod validation;
+pub use bevy_mod_mipmap_generator_derive::GetImages;
+pub use blob::{from_blob, to_blob};
+pub use color_grade::ColorGrade;
+#[cfg(feature = "config_file")]
+pub use config_file::MipmapSettingsFilePlugin;
+pub use error::MipmapError;
+#[cfg(feature = "gltf_extras")]
+pub use gltf_extras::{apply_gltf_material_extras, settings_from_gltf_extras};
+pub use gpu::{GpuMipmapGeneratorPlugin, GpuMipmapImages};
+#[cfg(feature = "gpu_validation")]
+pub use validation::{
+    MipmapValidationPlugin, MipmapValidationRequest, MipmapValidationRequests,
+    MipmapValidationResult, MipmapValidationResults,
+};
 
 #[derive(Resource, Deref)]
 pub struct DefaultSampler(ImageSamplerDescriptor);
 
 #[derive(Resource, Clone)]
 pub struct MipmapGeneratorSettings {
-    /// Valid values: 1, 2, 4, 8, and 16.
-    pub anisotropic_filtering: u16,
+    /// If false, `generate_mipmaps` only collects the materials that are loaded and does nothing
+    /// else, and `generate_image_mipmaps` leaves its queue alone. Useful for comparing with and
+    /// without mipmaps at runtime. Disabling doesn't remove mips that were already generated.
+    /// Materials loaded while disabled (minus the ones removed since), and tasks that were already
+    /// running, are processed once it's enabled again. They all start in the same frame, unless
+    /// `max_new_tasks_per_frame` is set.
+    pub enabled: bool,
+    /// Applied as the sampler's `anisotropy_clamp`. A `u16` can be converted with `.into()`.
+    pub anisotropic_filtering: AnisotropicFiltering,
+    /// Which images `anisotropic_filtering` is applied to, see `AnisotropyPolicy`.
+    pub anisotropy_policy: AnisotropyPolicy,
+    /// Anisotropic filtering is only applied to images whose width and height are both at least
+    /// this many pixels, smaller images keep their sampler's anisotropy. The default of 0 applies
+    /// it to every image allowed by `anisotropy_policy`.
+    pub anisotropy_min_resolution: u32,
+    /// If set, applied as the sampler's `compare` function, for textures sampled with comparison
+    /// like shadow maps or custom PCF lookups. Meant for `MipmapMaterialSettings` or the settings
+    /// of a `MipmapImageRequest`, a regular material texture can't be bound with a comparison
+    /// sampler.
+    pub sampler_compare: Option<ImageCompareFunction>,
+    /// If set, applied as the sampler's `border_color`. It's only used on axes with
+    /// `ImageAddressMode::ClampToBorder`, which needs `WgpuFeatures::ADDRESS_MODE_CLAMP_TO_BORDER`
+    /// (`ImageSamplerBorderColor::Zero` also needs `ADDRESS_MODE_CLAMP_TO_ZERO`). A warning is
+    /// logged if the sampler has no such axis.
+    pub sampler_border_color: Option<ImageSamplerBorderColor>,
     pub filter_type: FilterType,
+    /// If set, levels are downsampled with this filter instead of `filter_type`.
+    pub custom_filter: Option<CustomFilter>,
+    /// If set, `generate_mipmaps` ignores `filter_type` and picks a filter per image with
+    /// `recommended_filter`, using the roles reported by `GetImages::get_image_roles`.
+    pub use_recommended_filters: bool,
     pub minimum_mip_resolution: u32,
+    /// Divides the base resolution of every processed image by this before generating mips, so
+    /// the downsampled image becomes the new mip 0. For example 2 halves each side, dropping the
+    /// full resolution level to save memory on low end presets. Rounded down to a power of two,
+    /// 1 (default) keeps the original resolution. The divisor is reduced for images where it
+    /// would make either side smaller than 1 pixel, or smaller than 4 pixels with compression
+    /// enabled, so small images can still be compressed. Unlike `minimum_mip_resolution`, this
+    /// removes the largest levels instead of the smallest.
+    pub base_scale: u32,
+    /// If set, an unsharp mask with this strength is applied to each generated mip level to
+    /// counter the softening from repeated downsampling. Around 0.5 is a mild sharpen.
+    /// `generate_mipmaps` only applies this to images with `TextureRole::Color`.
+    pub post_downsample_sharpen: Option<f32>,
+    /// If set, this grade is applied to mip 0 of sRGB images before the rest of the chain is
+    /// generated, baking it into every level. `generate_mipmaps` only applies this to images with
+    /// `TextureRole::Color`, so data textures are never graded.
+    pub color_grade: Option<ColorGrade>,
+    /// If set, the alpha channel of each generated level of an RGBA image is scaled so the
+    /// fraction of pixels with an alpha above this cutoff matches mip 0. This keeps alpha tested
+    /// textures like foliage from thinning out at a distance. Use the material's alpha mask cutoff.
+    pub alpha_coverage_cutoff: Option<f32>,
+    /// If set, two channel (Rg8Unorm) images are treated as normal maps whose Z is reconstructed
+    /// in the shader, like BC5 normal maps. Each texel's full (x, y, z) vector is reconstructed
+    /// before downsampling and the result is renormalized before storing x and y again. Without
+    /// this, averaging only x and y shortens them and the reconstructed normals tilt towards +Z.
+    /// Rgba8 normal maps store the full vector in RGB, it's renormalized after downsampling so
+    /// averaged normals don't get shorter and darken the shading at a distance. Alpha is filtered
+    /// as is. `generate_mipmaps` only applies this to images with `TextureRole::Normal`.
+    pub renormalize_normals: bool,
+    /// If set, `generate_mipmaps` applies the smallest levels to the image as soon as they are
+    /// ready and fills in the larger ones over the following frames, using the texture view's
+    /// `base_mip_level` to hide levels that aren't ready yet. Every partial result re-uploads the
+    /// whole texture, so this trades total work for getting something on screen sooner.
+    pub progressive: bool,
+    /// If set, sRGB images are converted to linear before each downsample and back to sRGB after,
+    /// so colors are averaged the way the GPU blends them. Without this, averaging the sRGB values
+    /// directly darkens bright details and edges between contrasting colors in the lower mips.
+    /// Non-sRGB images with their sRGB sibling in `view_formats` count as sRGB, see
+    /// `ColorSpace::of_descriptor`.
+    pub linear_color_downsample: bool,
+    /// Transfer function that RGBA images are decoded from before each downsample and encoded
+    /// with again after, regardless of their format. Use `GammaMode::Custom` for assets authored
+    /// with a non-standard gamma. When not `GammaMode::Linear` (default) this takes precedence
+    /// over `linear_color_downsample`. `generate_mipmaps` only applies this to images with
+    /// `TextureRole::Color`.
+    pub downsample_gamma: GammaMode,
+    /// If set, 8-bit images are promoted to 16 bits per channel for the whole chain and each level
+    /// is only converted back to 8 bits when it's stored. This avoids the rounding error that
+    /// accumulates from downsampling each level from an already rounded one, which shows up as
+    /// banding in the lower mips of smooth gradients. Costs about twice the memory and time while
+    /// generating. Not used with `custom_filter`, which works on 8-bit data.
+    pub high_precision: bool,
     /// Set to Some(CompressionSpeed) to enable compression.
     /// The compress feature also needs to be enabled. Only BCn currently supported.
     /// Compression can take a long time, CompressionSpeed::UltraFast (default) is recommended.
@@ -39,31 +162,520 @@ pub struct MipmapGeneratorSettings {
     ///- Rg8Unorm -> Bc5RgUnorm
     ///- Rgba8Unorm -> Bc7RgbaUnorm
     ///- Rgba8UnormSrgb -> Bc7RgbaUnormSrgb
+    ///- Bgra8Unorm(Srgb) -> Bc7RgbaUnorm(Srgb)
+    ///- Rgba16Float, Rgba32Float -> Bc6hRgbUfloat
     pub compression: Option<CompressionSpeed>,
+    /// If set, the compression speed of each image is picked by calling this with the width and
+    /// height of mip 0, instead of using the speed from `compression`. Only used while
+    /// compression is enabled. For example, use a faster speed for large textures, which dominate
+    /// the total time, and a slower, higher quality one for small textures where it's cheap.
+    pub compression_speed_for_size: Option<CompressionSpeedFn>,
+    /// Which BCn format Rgba8 (and Bgra8) images are compressed to, see `CompressionFormat`.
+    /// Only used while compression is enabled. Other images, and images smaller than a 4x4
+    /// block, keep the format inferred from the source, so `Bc1` doesn't make R8 or Rg8 images
+    /// fail. Ignored with `force_output_format`.
+    pub compression_format: CompressionFormat,
+    /// Forces the color space of the compressed output, independent of the source format.
+    /// For example `Some(ColorSpace::Linear)` stores an Rgba8UnormSrgb source as Bc7RgbaUnorm when
+    /// gamma is applied in the shader. `None` (default) infers it from the source format.
+    /// Formats without an sRGB variant (BC4, BC5) can't be forced to sRGB, a warning is logged
+    /// and the inferred format is used instead.
+    pub compressed_color_space: Option<ColorSpace>,
+    /// What happens to images that can't be compressed while `compression` is set, see
+    /// `CompressionFallback`. Defaults to keeping their format.
+    pub compression_fallback: CompressionFallback,
+    /// If set, every processed image is stored in this format instead of the one inferred from
+    /// the source, for a fixed output format when shipping. `compressed_color_space` and
+    /// `compression_fallback` are ignored, and `compression` defaults to
+    /// `CompressionSpeed::default()` for BCn formats. Processing an image fails with an error if
+    /// the source can't be stored in this format without converting channels:
+    ///- Bc4RUnorm needs an R8 source, Bc5RgUnorm an Rg8 source.
+    ///- Bc3 and Bc7 need an Rgba8 source, and Bc1 an Rgba8 or Rg8 source. BC1 doesn't keep alpha.
+    ///- Uncompressed formats only match a source of the same format, which is left uncompressed.
+    ///
+    /// The sRGB suffix of the forced format is used as is. Since this applies to every image, it's
+    /// mostly useful in the per-request settings of `GenerateMipmaps`.
+    pub force_output_format: Option<TextureFormat>,
     /// If set, raw compressed image data will be cached in this directory.
     /// Images that are not BCn compressed are not cached.
     pub compressed_image_data_cache_path: Option<std::path::PathBuf>,
+    /// If set, the least recently used files in `compressed_image_data_cache_path` are deleted
+    /// before a new entry is written, until the directory fits in this many bytes with it.
+    /// Loading an entry counts as a use. `None` (default) lets the cache grow without limit.
+    pub cache_max_bytes: Option<u64>,
+    /// How compressed 2D arrays and cubemaps are cached. If set (default), each layer is its own
+    /// entry, keyed by the data of that layer and the settings. When only a few layers of a
+    /// runtime-updated array change, the others are loaded from the cache instead of being
+    /// compressed again. If not set, the whole array is a single entry, keyed by the data of all
+    /// layers and the settings. That's one file and one lookup per array, but changing any layer
+    /// compresses every layer again.
+    pub per_layer_cache: bool,
+    /// If set, compressed image data is also kept in memory for the lifetime of the cache, and
+    /// checked before the disk cache. This makes reloading a scene reuse compressions instantly,
+    /// and works without `compressed_image_data_cache_path`. Clones of the cache share storage.
+    pub memory_cache: Option<MemoryCache>,
     /// If low_quality is set, only 0.5 byte/px formats will be used (BC1, BC4) unless the alpha channel is in use, then BC3 will be used.
     /// When low quality is set, compression is generally faster than CompressionSpeed::UltraFast and CompressionSpeed is ignored.
     // TODO: low_quality normals should probably use BC5 or BC7 as they looks quite bad at BC1
     pub low_quality: bool,
+    /// If set (default), Rgba8 images whose alpha is 255 everywhere are compressed as opaque:
+    /// BC7 uses its opaque encoder settings and `low_quality` picks BC1 instead of BC3. If unset,
+    /// the alpha channel is always treated as in use, so every Rgba8 image gets the same format
+    /// and skips the scan over its alpha values.
+    pub detect_opaque_alpha: bool,
+    /// If set, opaque non-sRGB Rgba8 images whose red, green and blue are equal in every texel
+    /// are compressed to single channel BC4 instead of BC7, a quarter of the size. Only used while
+    /// compression is enabled. BC4 textures sample as `(r, 0, 0, 1)`, so this only works for
+    /// textures read through `.r` by the shader. Bevy's `StandardMaterial` reads color and
+    /// metallic/roughness from other channels, so only enable this for custom materials (or
+    /// per request settings) where that's the case. sRGB images are skipped since BC4 has no
+    /// sRGB variant.
+    pub detect_grayscale: bool,
+    /// If set, Rgba8 images are compressed to BC3 (DXT5) instead of BC7, for exporting to tools
+    /// and engines that only read the legacy DXT formats. Only used without `low_quality`, which
+    /// already picks BC1/BC3. `CompressionSpeed` doesn't apply to BC3.
+    pub use_bc3_for_rgba: bool,
+    /// If set, no additional mip levels are generated and only mip 0 is compressed, for images
+    /// that should be compressed but where mips would bleed, like sprite sheets.
+    pub compress_only: bool,
+    /// Allows generating mips for cubemaps, `check_image_compatible` rejects them otherwise.
+    /// A cubemap is a 2D image with a `TextureViewDimension::Cube` or `CubeArray` view, or with
+    /// 6 layers and no texture view descriptor. For now each face gets its own chain like the
+    /// layers of an array, so seams between faces can show up at the smallest levels. Filtering
+    /// across the face edges, so the chain is actually seamless, is a planned follow-up that
+    /// will be enabled by this flag.
+    pub cubemap_seamless: bool,
+    /// If set, processed images get this `asset_usage` instead of keeping their own. Use
+    /// `RenderAssetUsages::RENDER_WORLD` to free the CPU copy of the data once it's uploaded.
+    /// Bevy then removes the image from `Assets<Image>` after extracting it, so it can't be read
+    /// back, saved, validated with `MipmapValidationPlugin` or processed again afterwards, and
+    /// `Assets::get` returns `None` for it. Handles keep working for rendering.
+    pub asset_usage: Option<RenderAssetUsages>,
+    /// If set, called with the image's texture descriptor at the start of `generate_mips_texture`,
+    /// before anything else reads it. Use it to set a label for GPU debugging, extra usages or
+    /// view formats. Compressed and converted images still get their view formats cleared, and
+    /// changing the size, format or mip level count isn't supported since the data isn't changed
+    /// to match. The hook isn't part of the cache hash.
+    pub descriptor_hook: Option<DescriptorHook>,
+    /// If set, called with the index and image of every generated level (mip 0 isn't included),
+    /// after `post_downsample_sharpen` and alpha coverage and before the level is compressed.
+    /// Use it for custom sharpening, dithering and the like. The size and `DynamicImage` variant
+    /// of the level must not be changed. The next level is downsampled from the modified one,
+    /// except with `high_precision`, which keeps its own copy. The hook isn't part of the cache
+    /// hash, so use a separate `compressed_image_data_cache_path` for each hook.
+    pub level_hook: Option<LevelHook>,
+    /// Number of images expected to be processed at once. The task maps are allocated with this
+    /// capacity up front, to avoid reallocating while a large scene is loading. 0 (default) grows
+    /// them as needed.
+    pub task_capacity: usize,
+    /// If set, at most this many new tasks are spawned per frame by each generation system. The
+    /// remaining images are kept in a backlog and spawned over the following frames, which avoids
+    /// a hitch when a large scene loads at once. `None` (default) spawns all of them right away.
+    pub max_new_tasks_per_frame: Option<usize>,
+    /// If set, at most this many finished tasks are written back into `Assets<Image>` per frame by
+    /// each generation system. Each write triggers a (re)upload of the image, which is costly for
+    /// large textures. Finished tasks over the budget, and any partial results from `progressive`,
+    /// wait for a later frame. `None` (default) applies all finished tasks right away.
+    pub apply_budget_per_frame: Option<usize>,
+    /// If set, a warning naming the image is logged once for each task that hasn't finished this
+    /// long after it was spawned, to find the textures that hold up loading. The time includes
+    /// waiting for a free thread in the task pool. Slow tasks are not cancelled.
+    pub slow_task_warning: Option<Duration>,
+    /// If set, `track_mipmap_image_users` reverts images to their first level once no material
+    /// uses them anymore, freeing the memory of the rest of the chain. They get new mips when a
+    /// material uses them again. Compressed images and `GpuMipmapImages` keep their chain, since
+    /// their first level can't be mipmapped again on the cpu.
+    pub release_unused_mips: bool,
+    /// If set, the image each task started from is kept in `ProcessedImages`, so
+    /// `regenerate_all` can process it again after the settings changed at runtime. This keeps
+    /// an extra uncompressed copy of the first level of every processed image in memory.
+    pub keep_source_images: bool,
+    /// Order that new tasks are spawned in each frame, see `ProcessingOrder`.
+    pub processing_order: ProcessingOrder,
+    /// If set, images that can't be processed (unsupported format, dimension or layer count) are
+    /// treated as errors instead of being skipped or logged as warnings. Each error is logged and
+    /// sent as a `MipmapGenerationError` event.
+    pub strict: bool,
+    /// If set together with `strict`, the first error panics after it's reported, to fail fast
+    /// during development. Off by default, since a single bad asset then stops the app.
+    pub strict_panic: bool,
 }
 
+/// See `MipmapGeneratorSettings::compression_speed_for_size`.
+pub type CompressionSpeedFn = Arc<dyn Fn(u32, u32) -> CompressionSpeed + Send + Sync>;
+
+/// See `MipmapGeneratorSettings::descriptor_hook`.
+pub type DescriptorHook = Arc<dyn Fn(&mut TextureDescriptor<'static>) + Send + Sync>;
+
+/// See `MipmapGeneratorSettings::level_hook`.
+pub type LevelHook = Arc<dyn Fn(u32, &mut DynamicImage) + Send + Sync>;
+
 impl Default for MipmapGeneratorSettings {
     fn default() -> Self {
         Self {
+            enabled: true,
             // Default to 8x anisotropic filtering
-            anisotropic_filtering: 8,
+            anisotropic_filtering: AnisotropicFiltering::X8,
+            anisotropy_policy: AnisotropyPolicy::AllImages,
+            anisotropy_min_resolution: 0,
+            sampler_compare: None,
+            sampler_border_color: None,
             filter_type: FilterType::Triangle,
+            custom_filter: None,
+            use_recommended_filters: false,
             minimum_mip_resolution: 1,
+            base_scale: 1,
+            post_downsample_sharpen: None,
+            color_grade: None,
+            alpha_coverage_cutoff: None,
+            renormalize_normals: false,
+            progressive: false,
+            linear_color_downsample: false,
+            downsample_gamma: GammaMode::Linear,
+            high_precision: false,
             compression: None,
+            compression_speed_for_size: None,
+            compression_format: CompressionFormat::Auto,
+            compressed_color_space: None,
+            compression_fallback: CompressionFallback::KeepFormat,
+            force_output_format: None,
             compressed_image_data_cache_path: None,
+            cache_max_bytes: None,
+            per_layer_cache: true,
+            memory_cache: None,
             low_quality: false,
+            detect_opaque_alpha: true,
+            detect_grayscale: false,
+            use_bc3_for_rgba: false,
+            compress_only: false,
+            cubemap_seamless: false,
+            asset_usage: None,
+            descriptor_hook: None,
+            level_hook: None,
+            task_capacity: 0,
+            max_new_tasks_per_frame: None,
+            apply_budget_per_frame: None,
+            slow_task_warning: None,
+            release_unused_mips: false,
+            processing_order: ProcessingOrder::Unordered,
+            strict: false,
+            strict_panic: false,
+            keep_source_images: false,
+        }
+    }
+}
+
+impl MipmapGeneratorSettings {
+    /// Starts from the default settings, see the fields of `MipmapGeneratorSettings` for details.
+    pub fn builder() -> MipmapGeneratorSettingsBuilder {
+        MipmapGeneratorSettingsBuilder(MipmapGeneratorSettings::default())
+    }
+}
+
+/// Builder for `MipmapGeneratorSettings`, created with `MipmapGeneratorSettings::builder()`.
+/// Options that are `Option`s in the settings are enabled by calling the matching method.
+#[derive(Clone, Default)]
+pub struct MipmapGeneratorSettingsBuilder(MipmapGeneratorSettings);
+
+impl MipmapGeneratorSettingsBuilder {
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.0.enabled = enabled;
+        self
+    }
+
+    pub fn anisotropic_filtering(
+        mut self,
+        anisotropic_filtering: impl Into<AnisotropicFiltering>,
+    ) -> Self {
+        self.0.anisotropic_filtering = anisotropic_filtering.into();
+        self
+    }
+
+    pub fn anisotropy_policy(mut self, anisotropy_policy: AnisotropyPolicy) -> Self {
+        self.0.anisotropy_policy = anisotropy_policy;
+        self
+    }
+
+    pub fn anisotropy_min_resolution(mut self, anisotropy_min_resolution: u32) -> Self {
+        self.0.anisotropy_min_resolution = anisotropy_min_resolution;
+        self
+    }
+
+    pub fn sampler_compare(mut self, sampler_compare: ImageCompareFunction) -> Self {
+        self.0.sampler_compare = Some(sampler_compare);
+        self
+    }
+
+    pub fn sampler_border_color(mut self, sampler_border_color: ImageSamplerBorderColor) -> Self {
+        self.0.sampler_border_color = Some(sampler_border_color);
+        self
+    }
+
+    pub fn filter(mut self, filter_type: FilterType) -> Self {
+        self.0.filter_type = filter_type;
+        self
+    }
+
+    pub fn custom_filter(mut self, custom_filter: CustomFilter) -> Self {
+        self.0.custom_filter = Some(custom_filter);
+        self
+    }
+
+    pub fn use_recommended_filters(mut self, use_recommended_filters: bool) -> Self {
+        self.0.use_recommended_filters = use_recommended_filters;
+        self
+    }
+
+    pub fn minimum_mip_resolution(mut self, minimum_mip_resolution: u32) -> Self {
+        self.0.minimum_mip_resolution = minimum_mip_resolution;
+        self
+    }
+
+    pub fn base_scale(mut self, base_scale: u32) -> Self {
+        self.0.base_scale = base_scale;
+        self
+    }
+
+    pub fn sharpen(mut self, strength: f32) -> Self {
+        self.0.post_downsample_sharpen = Some(strength);
+        self
+    }
+
+    pub fn color_grade(mut self, color_grade: ColorGrade) -> Self {
+        self.0.color_grade = Some(color_grade);
+        self
+    }
+
+    pub fn alpha_coverage_cutoff(mut self, cutoff: f32) -> Self {
+        self.0.alpha_coverage_cutoff = Some(cutoff);
+        self
+    }
+
+    pub fn renormalize_normals(mut self, renormalize_normals: bool) -> Self {
+        self.0.renormalize_normals = renormalize_normals;
+        self
+    }
+
+    pub fn progressive(mut self, progressive: bool) -> Self {
+        self.0.progressive = progressive;
+        self
+    }
+
+    pub fn linear_color_downsample(mut self, linear_color_downsample: bool) -> Self {
+        self.0.linear_color_downsample = linear_color_downsample;
+        self
+    }
+
+    pub fn downsample_gamma(mut self, downsample_gamma: GammaMode) -> Self {
+        self.0.downsample_gamma = downsample_gamma;
+        self
+    }
+
+    pub fn high_precision(mut self, high_precision: bool) -> Self {
+        self.0.high_precision = high_precision;
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionSpeed) -> Self {
+        self.0.compression = Some(compression);
+        self
+    }
+
+    pub fn compression_speed_for_size(
+        mut self,
+        speed_for_size: impl Fn(u32, u32) -> CompressionSpeed + Send + Sync + 'static,
+    ) -> Self {
+        self.0.compression_speed_for_size = Some(Arc::new(speed_for_size));
+        self
+    }
+
+    pub fn compressed_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.0.compressed_color_space = Some(color_space);
+        self
+    }
+
+    pub fn compression_format(mut self, compression_format: CompressionFormat) -> Self {
+        self.0.compression_format = compression_format;
+        self
+    }
+
+    pub fn compression_fallback(mut self, compression_fallback: CompressionFallback) -> Self {
+        self.0.compression_fallback = compression_fallback;
+        self
+    }
+
+    pub fn force_output_format(mut self, format: TextureFormat) -> Self {
+        self.0.force_output_format = Some(format);
+        self
+    }
+
+    pub fn cache_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.0.compressed_image_data_cache_path = Some(path.into());
+        self
+    }
+
+    pub fn cache_max_bytes(mut self, cache_max_bytes: u64) -> Self {
+        self.0.cache_max_bytes = Some(cache_max_bytes);
+        self
+    }
+
+    pub fn per_layer_cache(mut self, per_layer_cache: bool) -> Self {
+        self.0.per_layer_cache = per_layer_cache;
+        self
+    }
+
+    pub fn memory_cache(mut self, memory_cache: MemoryCache) -> Self {
+        self.0.memory_cache = Some(memory_cache);
+        self
+    }
+
+    pub fn low_quality(mut self, low_quality: bool) -> Self {
+        self.0.low_quality = low_quality;
+        self
+    }
+
+    pub fn detect_opaque_alpha(mut self, detect_opaque_alpha: bool) -> Self {
+        self.0.detect_opaque_alpha = detect_opaque_alpha;
+        self
+    }
+
+    pub fn detect_grayscale(mut self, detect_grayscale: bool) -> Self {
+        self.0.detect_grayscale = detect_grayscale;
+        self
+    }
+
+    pub fn use_bc3_for_rgba(mut self, use_bc3_for_rgba: bool) -> Self {
+        self.0.use_bc3_for_rgba = use_bc3_for_rgba;
+        self
+    }
+
+    pub fn compress_only(mut self, compress_only: bool) -> Self {
+        self.0.compress_only = compress_only;
+        self
+    }
+
+    pub fn cubemap_seamless(mut self, cubemap_seamless: bool) -> Self {
+        self.0.cubemap_seamless = cubemap_seamless;
+        self
+    }
+
+    pub fn asset_usage(mut self, asset_usage: RenderAssetUsages) -> Self {
+        self.0.asset_usage = Some(asset_usage);
+        self
+    }
+
+    pub fn descriptor_hook(
+        mut self,
+        hook: impl Fn(&mut TextureDescriptor<'static>) + Send + Sync + 'static,
+    ) -> Self {
+        self.0.descriptor_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn level_hook(
+        mut self,
+        hook: impl Fn(u32, &mut DynamicImage) + Send + Sync + 'static,
+    ) -> Self {
+        self.0.level_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn task_capacity(mut self, task_capacity: usize) -> Self {
+        self.0.task_capacity = task_capacity;
+        self
+    }
+
+    pub fn max_new_tasks_per_frame(mut self, max_new_tasks_per_frame: usize) -> Self {
+        self.0.max_new_tasks_per_frame = Some(max_new_tasks_per_frame);
+        self
+    }
+
+    pub fn apply_budget_per_frame(mut self, apply_budget_per_frame: usize) -> Self {
+        self.0.apply_budget_per_frame = Some(apply_budget_per_frame);
+        self
+    }
+
+    pub fn slow_task_warning(mut self, slow_task_warning: Duration) -> Self {
+        self.0.slow_task_warning = Some(slow_task_warning);
+        self
+    }
+
+    pub fn release_unused_mips(mut self, release_unused_mips: bool) -> Self {
+        self.0.release_unused_mips = release_unused_mips;
+        self
+    }
+
+    pub fn processing_order(mut self, processing_order: ProcessingOrder) -> Self {
+        self.0.processing_order = processing_order;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.0.strict = strict;
+        self
+    }
+
+    pub fn strict_panic(mut self, strict_panic: bool) -> Self {
+        self.0.strict_panic = strict_panic;
+        self
+    }
+
+    pub fn keep_source_images(mut self, keep_source_images: bool) -> Self {
+        self.0.keep_source_images = keep_source_images;
+        self
+    }
+
+    pub fn build(self) -> MipmapGeneratorSettings {
+        self.0
+    }
+}
+
+/// In-memory cache of compressed image data, see `MipmapGeneratorSettings::memory_cache`.
+/// Keyed by the same hash as the disk cache.
+#[derive(Clone, Default)]
+pub struct MemoryCache(Arc<Mutex<HashMap<u64, Vec<u8>>>>);
+
+impl MemoryCache {
+    /// Number of cached images.
+    pub fn len(&self) -> usize {
+        self.0.lock().map_or(0, |cache| cache.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of the cached data.
+    pub fn size_bytes(&self) -> usize {
+        self.0
+            .lock()
+            .map_or(0, |cache| cache.values().map(Vec::len).sum())
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.0.lock() {
+            cache.clear();
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    fn get(&self, hash: u64) -> Option<Vec<u8>> {
+        self.0.lock().ok()?.get(&hash).cloned()
+    }
+
+    #[cfg(feature = "compress")]
+    fn insert(&self, hash: u64, data: &[u8]) {
+        if let Ok(mut cache) = self.0.lock() {
+            cache.insert(hash, data.to_vec());
         }
     }
 }
 
 #[derive(Default, Clone, Copy, Hash)]
+#[cfg_attr(
+    any(feature = "config_file", feature = "gltf_extras"),
+    derive(serde::Deserialize)
+)]
 pub enum CompressionSpeed {
     #[default]
     UltraFast,
@@ -94,409 +706,3469 @@ impl CompressionSpeed {
             }
         }
     }
-}
 
-///Mipmaps will not be generated for materials found on entities that also have the `NoMipmapGeneration` component.
-#[derive(Component)]
-pub struct NoMipmapGeneration;
+    #[cfg(feature = "compress")]
+    fn get_bc6h_encoder(&self) -> intel_tex_2::bc6h::EncodeSettings {
+        match self {
+            CompressionSpeed::UltraFast | CompressionSpeed::VeryFast => {
+                intel_tex_2::bc6h::very_fast_settings()
+            }
+            // intel_tex_2's name for ISPC's "fast" BC6H profile
+            CompressionSpeed::Fast => intel_tex_2::bc6h::very_settings(),
+            CompressionSpeed::Medium => intel_tex_2::bc6h::basic_settings(),
+            CompressionSpeed::Slow => intel_tex_2::bc6h::slow_settings(),
+        }
+    }
+}
 
-#[derive(Resource, Default)]
-pub struct MipmapGenerationProgress {
-    pub processed: u32,
-    pub total: u32,
-    /// Tracks the amount of bytes that have been cached since startup.
-    /// Used to warn at 1GB increments to avoid continuously caching images that change every frame.
-    pub cached_data_size_bytes: usize,
+/// Downsampling filters implemented by this crate, for cases `FilterType` doesn't cover.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CustomFilter {
+    /// Edge-aware (bilateral-style) box filter for ID and mask textures. Each source pixel is
+    /// weighted by how similar it is to the other pixels it's averaged with, so the majority side
+    /// of a hard boundary wins instead of being blended into an in-between value.
+    /// `range_sigma` is the color distance (0-1 per channel) at which pixels stop counting as
+    /// similar, around 0.1 keeps boundaries crisp. Intended for masks, not color: it removes the
+    /// smooth gradients a color texture needs and costs several times more than `Triangle`.
+    Bilateral { range_sigma: f32 },
+    /// A separate filter per channel, for channel packed textures where for example R is a mask
+    /// that needs `Nearest` and the other channels are smooth data. Indexed by the channels of the
+    /// image, so R8 images only use the first filter and Rg8 images the first two. Each level is
+    /// resized once per distinct filter, so this costs about that many times a single filter.
+    /// Only applies to the uncompressed levels before they're encoded (and compressed), and only
+    /// to 8 bit formats, others use the first filter. `high_precision` is not used.
+    PerChannel([FilterType; 4]),
+    /// Kaiser windowed sinc, the filter many offline texture tools use for a good balance between
+    /// aliasing and sharpness. `alpha` shapes the window: higher values suppress ringing more and
+    /// blur a bit more, around 4 is a common choice. The window reaches 3 pixels of the smaller
+    /// level to each side, like `Lanczos3`. Implemented as a separable resize in 32 bit float,
+    /// color is filtered in linear like `downsample_gamma` and `linear_color_downsample` describe.
+    /// Slower than `Lanczos3`, intended for offline bakes.
+    Kaiser { alpha: f32 },
 }
 
-fn format_bytes_size(size_in_bytes: usize) -> String {
-    if size_in_bytes < 1_000 {
-        format!("{}B", size_in_bytes)
-    } else if size_in_bytes < 1_000_000 {
-        format!("{:.2}KB", size_in_bytes as f64 / 1e3)
-    } else if size_in_bytes < 1_000_000_000 {
-        format!("{:.2}MB", size_in_bytes as f64 / 1e6)
-    } else {
-        format!("{:.2}GB", size_in_bytes as f64 / 1e9)
-    }
+/// Order that the images found in a frame are spawned as tasks in. Without a fixed order, the
+/// order follows material events and `get_images`, which can differ between runs. Tasks are
+/// spawned in this order, but with multiple threads they can still finish in a different one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config_file", derive(serde::Deserialize))]
+pub enum ProcessingOrder {
+    #[default]
+    Unordered,
+    /// Sorted by image asset id.
+    ById,
+    /// Smallest images (by pixel count) first, ties sorted by asset id.
+    BySize,
 }
 
-pub struct MipmapGeneratorPlugin;
-impl Plugin for MipmapGeneratorPlugin {
-    fn build(&self, app: &mut App) {
-        if let Some(image_plugin) = app
-            .init_resource::<MipmapGenerationProgress>()
-            .get_added_plugins::<ImagePlugin>()
-            .first()
-        {
-            let default_sampler = image_plugin.default_sampler.clone();
-            app.insert_resource(DefaultSampler(default_sampler))
-                .init_resource::<MipmapGeneratorSettings>();
-        } else {
-            warn!("No ImagePlugin found. Try adding MipmapGeneratorPlugin after DefaultPlugins");
+impl ProcessingOrder {
+    fn sort<T>(self, items: &mut [T], key: impl Fn(&T) -> (AssetId<Image>, &Image)) {
+        let pixels = |image: &Image| {
+            image.texture_descriptor.size.width as u64 * image.texture_descriptor.size.height as u64
+        };
+        match self {
+            ProcessingOrder::Unordered => (),
+            ProcessingOrder::ById => items.sort_by_key(|item| key(item).0),
+            ProcessingOrder::BySize => items.sort_by_key(|item| {
+                let (id, image) = key(item);
+                (pixels(image), id)
+            }),
         }
     }
 }
 
-#[derive(Clone, Resource)]
-#[cfg(feature = "debug_text")]
-pub struct MipmapGeneratorDebugTextPlugin;
-#[cfg(feature = "debug_text")]
-impl Plugin for MipmapGeneratorDebugTextPlugin {
-    fn build(&self, app: &mut App) {
-        app.insert_resource(self.clone())
-            .add_systems(Startup, init_loading_text)
-            .add_systems(Update, update_loading_text);
+/// What to do with images that can't be BCn compressed while compression is enabled, because
+/// their format has no BCn equivalent or they're smaller than a 4x4 block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressionFallback {
+    /// The image keeps its original format, uncompressed.
+    #[default]
+    KeepFormat,
+    /// The image is converted to uncompressed Rgba8Unorm, or Rgba8UnormSrgb for sRGB sources.
+    /// Channels are expanded like `DynamicImage::to_rgba8`: a single channel is copied to RGB and
+    /// a second channel becomes alpha. Higher precision formats are quantized to 8 bits.
+    Rgba8,
+}
+
+impl CompressionFallback {
+    /// The format an uncompressible image of `format` ends up with, `None` if it's unchanged.
+    fn format_for(self, format: TextureFormat) -> Option<TextureFormat> {
+        match self {
+            CompressionFallback::KeepFormat => None,
+            CompressionFallback::Rgba8 => {
+                Some(ColorSpace::of_format(format).apply_to_format(TextureFormat::Rgba8Unorm))
+            }
+        }
     }
 }
 
-#[cfg(feature = "debug_text")]
-fn init_loading_text(mut commands: Commands) {
-    commands
-        .spawn(NodeBundle {
-            style: Style {
-                left: Val::Px(1.5),
-                top: Val::Px(1.5),
-                ..default()
-            },
-            z_index: ZIndex::Global(-1),
-            ..default()
-        })
-        .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_sections(vec![TextSection {
-                    style: TextStyle {
-                        font_size: 18.0,
-                        color: Color::BLACK,
-                        ..default()
-                    },
-                    ..default()
-                }]),
-                MipmapGeneratorDebugLoadingText,
-            ));
-        });
-    commands
-        .spawn(NodeBundle::default())
-        .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_sections(vec![TextSection {
-                    style: TextStyle {
-                        font_size: 18.0,
-                        color: Color::WHITE,
-                        ..default()
-                    },
-                    ..default()
-                }]),
-                MipmapGeneratorDebugLoadingText,
-            ));
-        });
+/// BCn format for Rgba8 images, see `MipmapGeneratorSettings::compression_format`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "config_file", derive(serde::Deserialize))]
+pub enum CompressionFormat {
+    /// Picked from `low_quality` and `use_bc3_for_rgba`, see
+    /// `bcn_equivalent_format_of_dyn_image`.
+    #[default]
+    Auto,
+    /// 4 bits per pixel. Alpha is dropped, so only use it for opaque textures.
+    Bc1,
+    /// 8 bits per pixel, faster to encode than BC7 but lower quality. `CompressionSpeed`
+    /// doesn't apply.
+    Bc3,
+    /// 8 bits per pixel, the highest quality. Also used with `low_quality`.
+    Bc7,
 }
 
-#[cfg(feature = "debug_text")]
-#[derive(Component)]
-pub struct MipmapGeneratorDebugLoadingText;
-#[cfg(feature = "debug_text")]
-fn update_loading_text(
-    mut texts: Query<&mut Text, With<MipmapGeneratorDebugLoadingText>>,
-    progress: Res<MipmapGenerationProgress>,
-    time: Res<Time>,
-) {
-    for mut text in &mut texts {
-        text.sections[0].value = format!(
-            "bevy_mod_mipmap_generator progress: {} / {}\n{}",
-            progress.processed,
-            progress.total,
-            if progress.cached_data_size_bytes > 0 {
-                format!(
-                    "Cached this run: {}",
-                    format_bytes_size(progress.cached_data_size_bytes)
-                )
-            } else {
-                String::new()
+impl CompressionFormat {
+    /// The settings that make the encoder produce this format for an Rgba8 image.
+    fn apply_to(self, settings: &mut MipmapGeneratorSettings) {
+        match self {
+            CompressionFormat::Auto => (),
+            CompressionFormat::Bc1 => settings.low_quality = true,
+            CompressionFormat::Bc3 | CompressionFormat::Bc7 => {
+                settings.low_quality = false;
+                settings.use_bc3_for_rgba = self == CompressionFormat::Bc3;
             }
-        );
-        let alpha = if progress.processed == progress.total {
-            (text.sections[0].style.color.alpha() - time.delta_seconds() * 0.25).max(0.0)
-        } else {
-            1.0
+        }
+    }
+
+    /// The format an Rgba8 image in `color_space` is compressed to, `None` for `Auto`.
+    fn format(self, color_space: ColorSpace) -> Option<TextureFormat> {
+        let format = match self {
+            CompressionFormat::Auto => return None,
+            CompressionFormat::Bc1 => TextureFormat::Bc1RgbaUnorm,
+            CompressionFormat::Bc3 => TextureFormat::Bc3RgbaUnorm,
+            CompressionFormat::Bc7 => TextureFormat::Bc7RgbaUnorm,
         };
-        text.sections[0].style.color.set_alpha(alpha);
+        Some(color_space.apply_to_format(format))
     }
 }
 
-pub struct TaskData {
-    added_cache_size: usize,
-    image: Image,
+/// Anisotropic filtering level, limited to the values wgpu accepts for `anisotropy_clamp`.
+/// Other values fail sampler validation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "config_file", derive(serde::Deserialize))]
+pub enum AnisotropicFiltering {
+    /// No anisotropic filtering.
+    X1,
+    X2,
+    X4,
+    #[default]
+    X8,
+    X16,
 }
 
-#[derive(Resource, Default, Deref, DerefMut)]
-#[allow(clippy::type_complexity)]
-pub struct MipmapTasks<M: Material + GetImages>(
-    HashMap<Handle<Image>, (Task<TaskData>, Vec<Handle<M>>)>,
-);
-
-#[allow(clippy::too_many_arguments)]
+impl From<AnisotropicFiltering> for u16 {
+    fn from(anisotropic_filtering: AnisotropicFiltering) -> Self {
+        match anisotropic_filtering {
+            AnisotropicFiltering::X1 => 1,
+            AnisotropicFiltering::X2 => 2,
+            AnisotropicFiltering::X4 => 4,
+            AnisotropicFiltering::X8 => 8,
+            AnisotropicFiltering::X16 => 16,
+        }
+    }
+}
+
+/// Rounds down to the nearest valid level, so 3 becomes `X2` and anything above 16 is `X16`.
+impl From<u16> for AnisotropicFiltering {
+    fn from(anisotropic_filtering: u16) -> Self {
+        match anisotropic_filtering {
+            0..=1 => AnisotropicFiltering::X1,
+            2..=3 => AnisotropicFiltering::X2,
+            4..=7 => AnisotropicFiltering::X4,
+            8..=15 => AnisotropicFiltering::X8,
+            _ => AnisotropicFiltering::X16,
+        }
+    }
+}
+
+/// Which images get their sampler's `anisotropy_clamp` set to
+/// `MipmapGeneratorSettings::anisotropic_filtering`. Anisotropic filtering does little without
+/// mips.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config_file", derive(serde::Deserialize))]
+pub enum AnisotropyPolicy {
+    /// Every image found on a processed material, including images that can't be mipmapped,
+    /// for example because their format isn't supported.
+    #[default]
+    AllImages,
+    /// Only images that already have mips or that mips are generated for (on the CPU or GPU).
+    MipmappedOnly,
+    /// Samplers are left as they are.
+    None,
+}
+
+/// How a material uses an image. Used to pick per-image defaults like `recommended_filter`.
+///
+/// Each image is processed once, even if it's shared. If materials use the same image in roles
+/// that are processed differently (e.g. as base color and as metallic/roughness), the role it
+/// was first seen in is used and a warning is logged for the others. Use separate images, or
+/// pick the role with `MipmapImageOverride::role`, which silences the warning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureRole {
+    /// Color data that is viewed directly, like base color or emissive.
+    Color,
+    /// Tangent space normal maps.
+    Normal,
+    /// Other non-color data, like metallic/roughness, occlusion or masks.
+    Data,
+}
+
+impl TextureRole {
+    /// Fallback for images that a material doesn't report a role for.
+    /// sRGB formats are assumed to be color, everything else is treated as data.
+    pub fn from_format(format: TextureFormat) -> Self {
+        if format.is_srgb() {
+            TextureRole::Color
+        } else {
+            TextureRole::Data
+        }
+    }
+}
+
+/// Returns the recommended filter for downsampling an image of this format and role.
+/// - Color (Rgba8, Bgra8): `Lanczos3`, the sharpest result. Ringing is rarely noticeable in color
+///   textures.
+/// - Normal, Data, and any single or dual channel format: `Triangle`. Lanczos overshoot skews
+///   normals and packed values like roughness, which shows up as sparkling or dark edges.
+pub fn recommended_filter(format: TextureFormat, role: TextureRole) -> FilterType {
+    match (role, format) {
+        (
+            TextureRole::Color,
+            TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb,
+        ) => FilterType::Lanczos3,
+        _ => FilterType::Triangle,
+    }
+}
+
+/// Color space an image is processed and stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn of_format(format: TextureFormat) -> Self {
+        if format.is_srgb() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+
+    /// Like `of_format`, but a non-sRGB texture that lists its sRGB sibling in `view_formats` is
+    /// also treated as sRGB, since it's sampled through the sRGB view. This is common for render
+    /// targets that are reused as textures.
+    pub fn of_descriptor(descriptor: &TextureDescriptor) -> Self {
+        let srgb_format = descriptor.format.add_srgb_suffix();
+        if srgb_format != descriptor.format && descriptor.view_formats.contains(&srgb_format) {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::of_format(descriptor.format)
+        }
+    }
+
+    /// Returns the sRGB or non-sRGB sibling of `format`.
+    /// Formats without an sRGB variant (R8Unorm, Rg8Unorm, ...) are returned unchanged.
+    pub fn apply_to_format(self, format: TextureFormat) -> TextureFormat {
+        match self {
+            ColorSpace::Srgb => format.add_srgb_suffix(),
+            ColorSpace::Linear => format.remove_srgb_suffix(),
+        }
+    }
+}
+
+/// `FilterType` doesn't implement `Deserialize`.
+#[cfg(any(feature = "config_file", feature = "gltf_extras"))]
+#[derive(serde::Deserialize, Clone, Copy)]
+pub(crate) enum FilterTypeName {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+#[cfg(any(feature = "config_file", feature = "gltf_extras"))]
+impl From<FilterTypeName> for FilterType {
+    fn from(name: FilterTypeName) -> Self {
+        match name {
+            FilterTypeName::Nearest => FilterType::Nearest,
+            FilterTypeName::Triangle => FilterType::Triangle,
+            FilterTypeName::CatmullRom => FilterType::CatmullRom,
+            FilterTypeName::Gaussian => FilterType::Gaussian,
+            FilterTypeName::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Transfer function color values are stored with, see
+/// `MipmapGeneratorSettings::downsample_gamma`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "config_file", derive(serde::Deserialize))]
+pub enum GammaMode {
+    /// The sRGB transfer function, a linear segment near black and a 2.4 power curve above it.
+    Srgb,
+    /// Values are filtered as stored.
+    #[default]
+    Linear,
+    /// A pure power curve: stored values `c` are decoded with `c.powf(gamma)` and encoded again
+    /// with `c.powf(1.0 / gamma)`. For example `Custom(1.8)` for legacy Mac assets.
+    Custom(f32),
+}
+
+impl GammaMode {
+    fn decode(self, c: f32) -> f32 {
+        match self {
+            GammaMode::Srgb => srgb_to_linear(c),
+            GammaMode::Linear => c,
+            GammaMode::Custom(gamma) => c.max(0.0).powf(gamma),
+        }
+    }
+
+    fn encode(self, c: f32) -> f32 {
+        match self {
+            GammaMode::Srgb => linear_to_srgb(c),
+            GammaMode::Linear => c,
+            GammaMode::Custom(gamma) => c.clamp(0.0, 1.0).powf(1.0 / gamma),
+        }
+    }
+}
+
+/// Settings used by `generate_mipmaps::<M>` instead of the global `MipmapGeneratorSettings`
+/// resource, so different material types can be processed differently. Material types without
+/// this resource use the global settings.
+#[derive(Resource)]
+pub struct MipmapMaterialSettings<M: Material> {
+    pub settings: MipmapGeneratorSettings,
+    marker: PhantomData<M>,
+}
+
+impl<M: Material> MipmapMaterialSettings<M> {
+    pub fn new(settings: MipmapGeneratorSettings) -> Self {
+        MipmapMaterialSettings {
+            settings,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Per-image overrides, keyed by image. These take precedence over what the material reports and
+/// over the matching `MipmapGeneratorSettings` fields.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MipmapImageOverrides(pub HashMap<AssetId<Image>, MipmapImageOverride>);
+
+#[derive(Clone, Default, Debug)]
+pub struct MipmapImageOverride {
+    /// Overrides the role from `GetImages::get_image_roles`.
+    pub role: Option<TextureRole>,
+    /// Overrides the color space implied by the image's format. The processed image is stored with
+    /// the matching format, e.g. use `ColorSpace::Linear` to fix a normal map that was mistakenly
+    /// loaded as Rgba8UnormSrgb. A message is logged whenever this changes an image's format.
+    pub color_space: Option<ColorSpace>,
+    /// Overrides `MipmapGeneratorSettings::minimum_mip_resolution` for this image, e.g. to take a
+    /// detail noise texture all the way down to 1x1 while other images stop earlier.
+    pub minimum_mip_resolution: Option<u32>,
+}
+
+///Mipmaps will not be generated for materials found on entities that also have the `NoMipmapGeneration` component.
+#[derive(Component)]
+pub struct NoMipmapGeneration;
+
+/// Overrides some settings for the materials found on this entity. Fields that are `None` use
+/// the global settings (or `MipmapMaterialSettings`) as they are when the material is processed,
+/// so later changes to those still apply. Like `NoMipmapGeneration`, this has to be on the entity
+/// by the time `generate_mipmaps` sees the material being loaded. If several entities with
+/// different overrides use the same material, one of them is picked. Images are processed once,
+/// so an image shared by materials with different overrides uses the overrides of the material
+/// that was loaded first.
+#[derive(Component, Clone, Default)]
+pub struct MipmapSettingsOverride {
+    /// `Some(false)` disables compression, `Some(true)` enables it with `compression_speed`, or
+    /// the speed of the settings it's applied to (the default speed if those don't compress).
+    pub compression: Option<bool>,
+    /// Overrides the compression speed, also enables compression unless `compression` is
+    /// `Some(false)`.
+    pub compression_speed: Option<CompressionSpeed>,
+    pub compression_format: Option<CompressionFormat>,
+    pub low_quality: Option<bool>,
+    pub filter_type: Option<FilterType>,
+    pub minimum_mip_resolution: Option<u32>,
+}
+
+impl MipmapSettingsOverride {
+    /// `settings` with the fields that are set here replaced.
+    pub fn apply_to(&self, settings: &MipmapGeneratorSettings) -> MipmapGeneratorSettings {
+        let mut settings = settings.clone();
+        match (self.compression, self.compression_speed) {
+            (Some(false), _) => settings.compression = None,
+            (_, Some(speed)) => settings.compression = Some(speed),
+            (Some(true), None) => {
+                settings.compression = Some(settings.compression.unwrap_or_default());
+            }
+            (None, None) => (),
+        }
+        if let Some(compression_format) = self.compression_format {
+            settings.compression_format = compression_format;
+        }
+        if let Some(low_quality) = self.low_quality {
+            settings.low_quality = low_quality;
+        }
+        if let Some(filter_type) = self.filter_type {
+            settings.filter_type = filter_type;
+        }
+        if let Some(minimum_mip_resolution) = self.minimum_mip_resolution {
+            settings.minimum_mip_resolution = minimum_mip_resolution;
+        }
+        settings
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MipmapGenerationProgress {
+    pub processed: u32,
+    pub total: u32,
+    /// Tracks the amount of bytes that have been cached since startup.
+    /// Used to warn at 1GB increments to avoid continuously caching images that change every frame.
+    pub cached_data_size_bytes: usize,
+}
+
+/// Every image a generation task has finished for, with the result. Entries are added as tasks
+/// finish and are never removed, an image that's processed again replaces its entry.
+/// The handles are weak so this doesn't keep images loaded, use `Assets::get_strong_handle` to
+/// upgrade one that should be kept, and expect `Assets::get` to return `None` once it's unloaded.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ProcessedImages(pub HashMap<Handle<Image>, ProcessedInfo>);
+
+/// The materials that currently use each image, kept up to date by `track_mipmap_image_users`
+/// for every material type it's added for.
+#[derive(Resource, Default)]
+pub struct MipmapImageUsers {
+    images: HashMap<AssetId<Image>, HashSet<UntypedAssetId>>,
+    materials: HashMap<UntypedAssetId, Vec<AssetId<Image>>>,
+}
+
+impl MipmapImageUsers {
+    /// Number of materials using `image`.
+    pub fn user_count(&self, image: impl Into<AssetId<Image>>) -> usize {
+        self.images.get(&image.into()).map_or(0, HashSet::len)
+    }
+
+    /// Images used by at least one material.
+    pub fn used_images(&self) -> impl Iterator<Item = AssetId<Image>> + '_ {
+        self.images.keys().copied()
+    }
+
+    /// Sets the images `material` uses, returns the images that lost their last user.
+    fn set(
+        &mut self,
+        material: UntypedAssetId,
+        images: Vec<AssetId<Image>>,
+    ) -> Vec<AssetId<Image>> {
+        let mut unused = self.remove(material);
+        for image in &images {
+            self.images.entry(*image).or_default().insert(material);
+        }
+        unused.retain(|image| !self.images.contains_key(image));
+        self.materials.insert(material, images);
+        unused
+    }
+
+    /// Removes `material`, returns the images that lost their last user.
+    fn remove(&mut self, material: UntypedAssetId) -> Vec<AssetId<Image>> {
+        let mut unused = Vec::new();
+        for image in self.materials.remove(&material).unwrap_or_default() {
+            if let Some(users) = self.images.get_mut(&image) {
+                users.remove(&material);
+                if users.is_empty() {
+                    self.images.remove(&image);
+                    unused.push(image);
+                }
+            }
+        }
+        unused
+    }
+}
+
+/// Keeps `MipmapImageUsers` up to date from the material events of `M`. With
+/// `MipmapGeneratorSettings::release_unused_mips`, images whose last material is removed are
+/// reverted to their first level, and get new mips from `generate_mipmaps` if a material uses them
+/// again. Add it before `generate_mipmaps::<M>` for each material type to track. Images that are
+/// still being processed when their last material is removed get their mips anyway.
+#[allow(clippy::too_many_arguments)]
+pub fn track_mipmap_image_users<M: Material + GetImages>(
+    mut material_events: EventReader<AssetEvent<M>>,
+    materials: Res<Assets<M>>,
+    mut images: ResMut<Assets<Image>>,
+    mut users: ResMut<MipmapImageUsers>,
+    mut processed: ResMut<ProcessedImages>,
+    settings: Res<MipmapGeneratorSettings>,
+    material_settings: Option<Res<MipmapMaterialSettings<M>>>,
+    gpu_images: Option<Res<GpuMipmapImages>>,
+) {
+    let settings = material_settings
+        .as_ref()
+        .map_or(&*settings, |material_settings| &material_settings.settings);
+    let mut unused = Vec::new();
+    for event in material_events.read() {
+        match event {
+            AssetEvent::Added { id }
+            | AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Modified { id } => {
+                if let Some(material) = materials.get(*id) {
+                    let material_images = material.get_images().iter().map(|h| h.id()).collect();
+                    unused.extend(users.set(id.untyped(), material_images));
+                }
+            }
+            AssetEvent::Removed { id } => unused.extend(users.remove(id.untyped())),
+            _ => (),
+        }
+    }
+    if !settings.release_unused_mips {
+        return;
+    }
+    for image_id in unused {
+        if users.user_count(image_id) > 0
+            || gpu_images
+                .as_ref()
+                .is_some_and(|gpu_images| gpu_images.contains(&image_id))
+        {
+            continue;
+        }
+        // Check with get() first so images that are kept don't trigger change detection
+        let Some(image) = images.get(image_id) else {
+            continue;
+        };
+        if image.texture_descriptor.mip_level_count == 1 || image.is_compressed() {
+            continue;
+        }
+        if let Some(image) = images.get_mut(image_id) {
+            release_mips(image);
+            processed.remove(&Handle::Weak(image_id));
+            debug!("Released the mips of unused image {:?}", image_id);
+        }
+    }
+}
+
+/// Reverts `image` to its first level. The levels of each layer are stored one layer after
+/// another, so level 0 of every layer is kept, not just the start of the data.
+fn release_mips(image: &mut Image) {
+    let descriptor = &mut image.texture_descriptor;
+    let (width, height, format) = (
+        descriptor.size.width,
+        descriptor.size.height,
+        descriptor.format,
+    );
+    let level_size = mip_chain_byte_size(width, height, format, 1);
+    let layer_size = mip_chain_byte_size(width, height, format, descriptor.mip_level_count);
+    if layer_size == 0 {
+        return;
+    }
+    image.data = image
+        .data
+        .chunks(layer_size)
+        .take(descriptor.size.depth_or_array_layers as usize)
+        .flat_map(|layer| &layer[..level_size.min(layer.len())])
+        .copied()
+        .collect();
+    descriptor.mip_level_count = 1;
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessedInfo {
+    pub format: TextureFormat,
+    pub mip_level_count: u32,
+    /// Size of the image data including all mip levels.
+    pub size_bytes: usize,
+    /// The image the task started from, if `MipmapGeneratorSettings::keep_source_images` is set.
+    pub source: Option<Image>,
+}
+
+fn format_bytes_size(size_in_bytes: usize) -> String {
+    if size_in_bytes < 1_000 {
+        format!("{}B", size_in_bytes)
+    } else if size_in_bytes < 1_000_000 {
+        format!("{:.2}KB", size_in_bytes as f64 / 1e3)
+    } else if size_in_bytes < 1_000_000_000 {
+        format!("{:.2}MB", size_in_bytes as f64 / 1e6)
+    } else {
+        format!("{:.2}GB", size_in_bytes as f64 / 1e9)
+    }
+}
+
+pub struct MipmapGeneratorPlugin;
+impl Plugin for MipmapGeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        let default_sampler = match app.get_added_plugins::<ImagePlugin>().first() {
+            Some(image_plugin) => image_plugin.default_sampler.clone(),
+            None => {
+                // Keep going with the sampler ImagePlugin defaults to, so the systems don't
+                // panic on a missing DefaultSampler if the plugin order is wrong.
+                warn!(
+                    "No ImagePlugin found, assuming its default sampler. Try adding \
+                    MipmapGeneratorPlugin after DefaultPlugins"
+                );
+                ImagePlugin::default().default_sampler
+            }
+        };
+        app.init_resource::<MipmapGenerationProgress>()
+            .init_resource::<ProcessedImages>()
+            .init_resource::<MipmapImageUsers>()
+            .init_resource::<MipmapImageOverrides>()
+            .add_event::<MipmapGenerationError>()
+            .add_event::<MipmapGenerationStarted>()
+            .add_event::<MipmapsGenerated>()
+            .insert_resource(DefaultSampler(default_sampler))
+            .init_resource::<MipmapGeneratorSettings>()
+            .init_resource::<MipmapImageQueue>()
+            .init_resource::<MipmapImageTasks>()
+            .add_event::<GenerateMipmaps>()
+            .add_systems(
+                Update,
+                (queue_requested_mipmaps, generate_image_mipmaps).chain(),
+            );
+    }
+}
+
+/// Generates mipmaps for the images of `UiImage` nodes. Requires `MipmapGeneratorPlugin`.
+#[cfg(feature = "ui")]
+pub struct MipmapGeneratorUiPlugin;
+#[cfg(feature = "ui")]
+impl Plugin for MipmapGeneratorUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            queue_ui_image_mipmaps.before(generate_image_mipmaps),
+        );
+    }
+}
+
+/// Generates mipmaps for the images of `Sprite` entities, so sprites don't shimmer when scaled
+/// down. Requires `MipmapGeneratorPlugin`.
+#[cfg(feature = "sprite")]
+pub struct MipmapGeneratorSpritePlugin;
+#[cfg(feature = "sprite")]
+impl Plugin for MipmapGeneratorSpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, queue_sprite_mipmaps.before(generate_image_mipmaps));
+    }
+}
+
+#[derive(Clone, Resource)]
+#[cfg(feature = "debug_text")]
+pub struct MipmapGeneratorDebugTextPlugin;
+#[cfg(feature = "debug_text")]
+impl Plugin for MipmapGeneratorDebugTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.clone())
+            .add_systems(Startup, init_loading_text)
+            .add_systems(Update, update_loading_text);
+    }
+}
+
+#[cfg(feature = "debug_text")]
+fn init_loading_text(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                left: Val::Px(1.5),
+                top: Val::Px(1.5),
+                ..default()
+            },
+            z_index: ZIndex::Global(-1),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections(vec![TextSection {
+                    style: TextStyle {
+                        font_size: 18.0,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                    ..default()
+                }]),
+                MipmapGeneratorDebugLoadingText,
+            ));
+        });
+    commands
+        .spawn(NodeBundle::default())
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections(vec![TextSection {
+                    style: TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                    ..default()
+                }]),
+                MipmapGeneratorDebugLoadingText,
+            ));
+        });
+}
+
+#[cfg(feature = "debug_text")]
+#[derive(Component)]
+pub struct MipmapGeneratorDebugLoadingText;
+#[cfg(feature = "debug_text")]
+fn update_loading_text(
+    mut texts: Query<&mut Text, With<MipmapGeneratorDebugLoadingText>>,
+    progress: Res<MipmapGenerationProgress>,
+    time: Res<Time>,
+) {
+    for mut text in &mut texts {
+        text.sections[0].value = format!(
+            "bevy_mod_mipmap_generator progress: {} / {}\n{}",
+            progress.processed,
+            progress.total,
+            if progress.cached_data_size_bytes > 0 {
+                format!(
+                    "Cached this run: {}",
+                    format_bytes_size(progress.cached_data_size_bytes)
+                )
+            } else {
+                String::new()
+            }
+        );
+        let alpha = if progress.processed == progress.total {
+            (text.sections[0].style.color.alpha() - time.delta_seconds() * 0.25).max(0.0)
+        } else {
+            1.0
+        };
+        text.sections[0].style.color.set_alpha(alpha);
+    }
+}
+
+pub struct TaskData {
+    added_cache_size: usize,
+    image: Image,
+    source: Option<Image>,
+    /// Set if generation failed and `MipmapGeneratorSettings::strict` is enabled.
+    error: Option<String>,
+}
+
+pub struct MipmapTask<M: Material + GetImages> {
+    pub task: Task<TaskData>,
+    /// Materials using this image, touched when the image is updated so they pick up the change.
+    pub material_handles: Vec<Handle<M>>,
+    /// Partial results sent by the task when `MipmapGeneratorSettings::progressive` is enabled.
+    pub partial_images: Option<Mutex<Receiver<Image>>>,
+    /// Used for `MipmapGeneratorSettings::slow_task_warning`.
+    pub spawned_at: Instant,
+    slow_warning_logged: bool,
+    /// Role the image is processed in, other roles only get a warning.
+    role: TextureRole,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MipmapTasks<M: Material + GetImages>(HashMap<Handle<Image>, MipmapTask<M>>);
+
+impl<M: Material + GetImages> MipmapTasks<M> {
+    /// Number of tasks that are running or finished but not yet written back.
+    pub fn in_flight(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Spawns mipmap tasks for the images of new materials of type `M`, then applies the finished
+/// ones. Runs `spawn_mipmap_tasks` and `apply_mipmap_results` back to back, use those instead to
+/// schedule the two halves separately.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_mipmaps<M: Material + GetImages>(
     mut commands: Commands,
     mut material_events: EventReader<AssetEvent<M>>,
     mut materials: ResMut<Assets<M>>,
     no_mipmap: Query<&Handle<M>, With<NoMipmapGeneration>>,
+    settings_overrides: Query<(&Handle<M>, &MipmapSettingsOverride)>,
     mut images: ResMut<Assets<Image>>,
     default_sampler: Res<DefaultSampler>,
     mut progress: ResMut<MipmapGenerationProgress>,
+    mut processed: ResMut<ProcessedImages>,
     settings: Res<MipmapGeneratorSettings>,
+    material_settings: Option<Res<MipmapMaterialSettings<M>>>,
     mut tasks_res: Option<ResMut<MipmapTasks<M>>>,
+    gpu_images: Option<Res<GpuMipmapImages>>,
+    image_overrides: Option<Res<MipmapImageOverrides>>,
+    mut events: MipmapEvents,
+    mut backlog: Local<Vec<MipmapJob<Vec<Handle<M>>>>>,
+    mut pending: Local<Vec<AssetId<M>>>,
 ) {
-    let mut new_tasks = MipmapTasks(HashMap::new());
+    let settings = material_settings
+        .as_ref()
+        .map_or(&*settings, |material_settings| &material_settings.settings);
+    // Materials loaded while disabled are kept for when it's enabled again
+    collect_material_events(&mut material_events, &mut pending);
+    if !settings.enabled {
+        return;
+    }
+    let material_ids = std::mem::take(&mut *pending);
+
+    let mut new_tasks = MipmapTasks(HashMap::with_capacity(settings.task_capacity));
+    let tasks = if let Some(ref mut tasks) = tasks_res {
+        tasks
+    } else {
+        &mut new_tasks
+    };
+
+    spawn_material_tasks(
+        settings,
+        tasks,
+        &material_ids,
+        &mut materials,
+        &no_mipmap,
+        &settings_overrides,
+        &mut images,
+        &default_sampler,
+        &mut progress,
+        gpu_images.as_deref(),
+        image_overrides.as_deref(),
+        &mut events,
+        &mut backlog,
+    );
+    apply_material_tasks(
+        settings,
+        tasks,
+        &mut materials,
+        &mut images,
+        &mut progress,
+        &mut processed,
+        &mut events,
+    );
+
+    if tasks_res.is_none() {
+        commands.insert_resource(new_tasks);
+    }
+}
+
+/// The first half of `generate_mipmaps`: reads the material events of `M`, applies the sampler
+/// settings and spawns tasks into `MipmapTasks<M>`. Finished tasks are left for
+/// `apply_mipmap_results`, which can be scheduled separately, for example in a later schedule.
+/// Don't add both of these and `generate_mipmaps` for the same material type.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_mipmap_tasks<M: Material + GetImages>(
+    mut commands: Commands,
+    mut material_events: EventReader<AssetEvent<M>>,
+    mut materials: ResMut<Assets<M>>,
+    no_mipmap: Query<&Handle<M>, With<NoMipmapGeneration>>,
+    settings_overrides: Query<(&Handle<M>, &MipmapSettingsOverride)>,
+    mut images: ResMut<Assets<Image>>,
+    default_sampler: Res<DefaultSampler>,
+    mut progress: ResMut<MipmapGenerationProgress>,
+    settings: Res<MipmapGeneratorSettings>,
+    material_settings: Option<Res<MipmapMaterialSettings<M>>>,
+    mut tasks_res: Option<ResMut<MipmapTasks<M>>>,
+    gpu_images: Option<Res<GpuMipmapImages>>,
+    image_overrides: Option<Res<MipmapImageOverrides>>,
+    mut events: MipmapEvents,
+    mut backlog: Local<Vec<MipmapJob<Vec<Handle<M>>>>>,
+    mut pending: Local<Vec<AssetId<M>>>,
+) {
+    let settings = material_settings
+        .as_ref()
+        .map_or(&*settings, |material_settings| &material_settings.settings);
+    // Materials loaded while disabled are kept for when it's enabled again
+    collect_material_events(&mut material_events, &mut pending);
+    if !settings.enabled {
+        return;
+    }
+    let material_ids = std::mem::take(&mut *pending);
+
+    let mut new_tasks = MipmapTasks(HashMap::with_capacity(settings.task_capacity));
+    let tasks = if let Some(ref mut tasks) = tasks_res {
+        tasks
+    } else {
+        &mut new_tasks
+    };
+
+    spawn_material_tasks(
+        settings,
+        tasks,
+        &material_ids,
+        &mut materials,
+        &no_mipmap,
+        &settings_overrides,
+        &mut images,
+        &default_sampler,
+        &mut progress,
+        gpu_images.as_deref(),
+        image_overrides.as_deref(),
+        &mut events,
+        &mut backlog,
+    );
+
+    if tasks_res.is_none() {
+        commands.insert_resource(new_tasks);
+    }
+}
+
+/// The second half of `generate_mipmaps`: writes finished (and partial) results of the tasks
+/// spawned by `spawn_mipmap_tasks::<M>` into `Assets<Image>` and touches the materials using them.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_mipmap_results<M: Material + GetImages>(
+    mut materials: ResMut<Assets<M>>,
+    mut images: ResMut<Assets<Image>>,
+    mut progress: ResMut<MipmapGenerationProgress>,
+    mut processed: ResMut<ProcessedImages>,
+    settings: Res<MipmapGeneratorSettings>,
+    material_settings: Option<Res<MipmapMaterialSettings<M>>>,
+    tasks: Option<ResMut<MipmapTasks<M>>>,
+    mut events: MipmapEvents,
+) {
+    let settings = material_settings
+        .as_ref()
+        .map_or(&*settings, |material_settings| &material_settings.settings);
+    let Some(mut tasks) = tasks else {
+        return;
+    };
+    if !settings.enabled {
+        return;
+    }
+    apply_material_tasks(
+        settings,
+        &mut tasks,
+        &mut materials,
+        &mut images,
+        &mut progress,
+        &mut processed,
+        &mut events,
+    );
+}
+
+/// Spawns new tasks into `tasks` for the images of every material of type `M` that have a source
+/// kept in `processed`, see `MipmapGeneratorSettings::keep_source_images`. Use it to apply changed
+/// settings to images that already have mips, the results are written back by `generate_mipmaps`
+/// or `apply_mipmap_results` like any other task. Tasks still running for these images are
+/// replaced. Per entity `MipmapSettingsOverride`s aren't consulted, `settings` is used for every
+/// material. New tasks are added to `progress.total`, replaced ones are already counted there.
+/// Returns the images tasks were spawned for.
+pub fn regenerate_all<M: Material + GetImages>(
+    materials: &Assets<M>,
+    processed: &ProcessedImages,
+    settings: &MipmapGeneratorSettings,
+    image_overrides: Option<&MipmapImageOverrides>,
+    tasks: &mut MipmapTasks<M>,
+    progress: &mut MipmapGenerationProgress,
+) -> Vec<Handle<Image>> {
+    let mut jobs: Vec<MipmapJob<Vec<Handle<M>>>> = Vec::new();
+    for (material_id, material) in materials.iter() {
+        let roles = material.get_image_roles();
+        for image_h in material.get_images() {
+            if let Some(job) = jobs.iter_mut().find(|job| job.image_h == *image_h) {
+                job.users.push(Handle::Weak(material_id));
+                continue;
+            }
+            let Some(mut image) = processed.get(image_h).and_then(|info| info.source.clone())
+            else {
+                continue;
+            };
+            let role = roles
+                .iter()
+                .find(|(h, _)| *h == image_h)
+                .map(|(_, role)| *role);
+            let settings =
+                resolve_image_settings(image_h, &mut image, settings, image_overrides, role);
+            let role = image_role(
+                image_h,
+                image.texture_descriptor.format,
+                image_overrides,
+                role,
+            );
+            jobs.push(MipmapJob {
+                image_h: image_h.clone_weak(),
+                image,
+                settings,
+                role,
+                users: vec![Handle::Weak(material_id)],
+            });
+        }
+    }
+
+    let mut spawned = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        let (task, partial_images) = spawn_mipmap_task(job.image, job.settings);
+        spawned.push(job.image_h.clone_weak());
+        let replaced = tasks.insert(
+            job.image_h,
+            MipmapTask {
+                task,
+                material_handles: job.users,
+                partial_images,
+                spawned_at: Instant::now(),
+                slow_warning_logged: false,
+                role: job.role,
+            },
+        );
+        // The replaced task is dropped without finishing, its count carries over to the new one
+        if replaced.is_none() {
+            progress.total += 1;
+        }
+    }
+    spawned
+}
+
+/// Calls `regenerate_all` for materials of type `M` whenever `MipmapGeneratorSettings` (or
+/// `MipmapMaterialSettings<M>`) changes, so tweaking settings at runtime, e.g. in an editor or
+/// through `MipmapSettingsFilePlugin`, applies to images that already have mips. Requires
+/// `MipmapGeneratorSettings::keep_source_images`. Add it next to `generate_mipmaps::<M>`.
+#[allow(clippy::too_many_arguments)]
+pub fn regenerate_mipmaps_on_settings_change<M: Material + GetImages>(
+    materials: Res<Assets<M>>,
+    processed: Res<ProcessedImages>,
+    settings: Res<MipmapGeneratorSettings>,
+    material_settings: Option<Res<MipmapMaterialSettings<M>>>,
+    image_overrides: Option<Res<MipmapImageOverrides>>,
+    tasks: Option<ResMut<MipmapTasks<M>>>,
+    mut progress: ResMut<MipmapGenerationProgress>,
+    mut started: EventWriter<MipmapGenerationStarted>,
+) {
+    let changed = match &material_settings {
+        Some(material_settings) => material_settings.is_changed() && !material_settings.is_added(),
+        None => settings.is_changed() && !settings.is_added(),
+    };
+    let settings = material_settings
+        .as_ref()
+        .map_or(&*settings, |material_settings| &material_settings.settings);
+    // Without tasks nothing was processed for this material type yet
+    let Some(mut tasks) = tasks else {
+        return;
+    };
+    if !changed || !settings.enabled {
+        return;
+    }
+    let spawned = regenerate_all(
+        &materials,
+        &processed,
+        settings,
+        image_overrides.as_deref(),
+        &mut tasks,
+        &mut progress,
+    );
+    if !spawned.is_empty() {
+        info!("Regenerating mipmaps of {} images", spawned.len());
+    }
+    started.send_batch(
+        spawned
+            .into_iter()
+            .map(|image| MipmapGenerationStarted { image }),
+    );
+}
+
+/// Result of `bake_all`.
+#[derive(Clone, Debug, Default)]
+pub struct BakeReport {
+    /// Images that got mips (or were compressed).
+    pub processed: usize,
+    /// Images that already had mips or can't be processed.
+    pub skipped: usize,
+    /// Images whose generation failed, with the error.
+    pub errors: Vec<(AssetId<Image>, String)>,
+    /// Amount of data added to the disk cache.
+    pub added_cache_size: usize,
+}
+
+/// Synchronously processes the images of every material of type `M`, for offline baking without
+/// the event driven `generate_mipmaps`. Each image is processed once even if it's shared, with
+/// the same sampler settings and per role settings as `generate_mipmaps`, and the materials using
+/// processed images are touched so they pick up the change. `MipmapImageOverrides`,
+/// `NoMipmapGeneration` and `GpuMipmapImages` aren't consulted.
+pub fn bake_all<M: Material + GetImages>(
+    materials: &mut Assets<M>,
+    images: &mut Assets<Image>,
+    default_sampler: &DefaultSampler,
+    settings: &MipmapGeneratorSettings,
+) -> BakeReport {
+    bake_all_with_progress(materials, images, default_sampler, settings, |_, _, _| {})
+}
+
+/// Same as `bake_all`, but calls `progress(done, total, current)` after each image is finished,
+/// whether it was processed, skipped or failed. `total` is the number of loaded images used by
+/// the materials. The callback is called synchronously on the thread doing the baking, between
+/// images, so a slow callback slows down the bake.
+pub fn bake_all_with_progress<M: Material + GetImages>(
+    materials: &mut Assets<M>,
+    images: &mut Assets<Image>,
+    default_sampler: &DefaultSampler,
+    settings: &MipmapGeneratorSettings,
+    mut progress: impl FnMut(usize, usize, &Handle<Image>),
+) -> BakeReport {
+    let mut report = BakeReport::default();
+    let mut seen = HashMap::new();
+    let mut processed = HashSet::new();
+    // Collected first so the total is known before the first image
+    let mut jobs = Vec::new();
+    for (_, material) in materials.iter() {
+        let roles = material.get_image_roles();
+        for image_h in material.get_images() {
+            let Some(image) = images.get(image_h) else {
+                continue;
+            };
+            if let Some(role) = seen.get(&image_h.id()) {
+                warn_role_conflicts(image_h, *role, &roles, None);
+                continue;
+            }
+            let role = roles
+                .iter()
+                .find(|(h, _)| *h == image_h)
+                .map(|(_, role)| *role);
+            seen.insert(
+                image_h.id(),
+                image_role(image_h, image.texture_descriptor.format, None, role),
+            );
+            jobs.push((image_h.clone(), role));
+        }
+    }
+    let total = jobs.len();
+    for (done, (image_h, role)) in jobs.iter().enumerate() {
+        if let Some(image) = images.get_mut(image_h) {
+            bake_image(
+                image_h,
+                image,
+                *role,
+                default_sampler,
+                settings,
+                &mut report,
+                &mut processed,
+            );
+        }
+        progress(done + 1, total, image_h);
+    }
+    // Touch materials to trigger change detection
+    let updated_materials = materials
+        .iter()
+        .filter(|(_, material)| {
+            material
+                .get_images()
+                .iter()
+                .any(|image_h| processed.contains(&image_h.id()))
+        })
+        .map(|(material_id, _)| material_id)
+        .collect::<Vec<_>>();
+    for material_id in updated_materials {
+        let _ = materials.get_mut(material_id);
+    }
+    report
+}
+
+fn bake_image(
+    image_h: &Handle<Image>,
+    image: &mut Image,
+    role: Option<TextureRole>,
+    default_sampler: &DefaultSampler,
+    settings: &MipmapGeneratorSettings,
+    report: &mut BakeReport,
+    processed: &mut HashSet<AssetId<Image>>,
+) {
+    apply_sampler_settings(image, default_sampler, settings, false, None);
+    if image.texture_descriptor.mip_level_count != 1 {
+        report.skipped += 1;
+        return;
+    }
+    if let Err(e) = check_image_compatible(image, settings) {
+        if settings.strict {
+            report.errors.push((image_h.id(), e.to_string()));
+        } else {
+            report.skipped += 1;
+        }
+        return;
+    }
+    let image_settings = resolve_image_settings(image_h, image, settings, None, role);
+    match generate_mips_texture(image, &image_settings, &mut report.added_cache_size) {
+        Ok(()) => {
+            report.processed += 1;
+            processed.insert(image_h.id());
+        }
+        Err(e) => report.errors.push((image_h.id(), e.to_string())),
+    }
+}
+
+/// Adds the materials that were added or finished loading to `pending`, the ones
+/// `generate_mipmaps` processes, and drops the ones that were removed since.
+fn collect_material_events<M: Material>(
+    material_events: &mut EventReader<AssetEvent<M>>,
+    pending: &mut Vec<AssetId<M>>,
+) {
+    for event in material_events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::LoadedWithDependencies { id } => {
+                pending.push(*id);
+            }
+            AssetEvent::Removed { id } => pending.retain(|pending| pending != id),
+            _ => (),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_material_tasks<M: Material + GetImages>(
+    settings: &MipmapGeneratorSettings,
+    tasks: &mut MipmapTasks<M>,
+    material_ids: &[AssetId<M>],
+    materials: &mut Assets<M>,
+    no_mipmap: &Query<&Handle<M>, With<NoMipmapGeneration>>,
+    settings_overrides: &Query<(&Handle<M>, &MipmapSettingsOverride)>,
+    images: &mut Assets<Image>,
+    default_sampler: &DefaultSampler,
+    progress: &mut MipmapGenerationProgress,
+    gpu_images: Option<&GpuMipmapImages>,
+    image_overrides: Option<&MipmapImageOverrides>,
+    events: &mut MipmapEvents,
+    backlog: &mut Vec<MipmapJob<Vec<Handle<M>>>>,
+) {
+    // Images to spawn tasks for, collected first so they can be spawned in processing_order.
+    // Starts with the jobs deferred by max_new_tasks_per_frame in previous frames.
+    let mut jobs = std::mem::take(&mut *backlog);
+
+    'outer: for material_h in material_ids {
+        for m in no_mipmap.iter() {
+            if m.id() == *material_h {
+                continue 'outer;
+            }
+        }
+        let overridden;
+        let settings = match settings_overrides
+            .iter()
+            .find(|(m, _)| m.id() == *material_h)
+        {
+            Some((_, settings_override)) => {
+                overridden = settings_override.apply_to(settings);
+                &overridden
+            }
+            None => settings,
+        };
+        if !settings.enabled {
+            continue;
+        }
+        // get_mut(material_h) here so we see the filtering right away
+        // and even if mipmaps aren't made, we still get the filtering
+        if let Some(material) = materials.get_mut(*material_h) {
+            let roles = material.get_image_roles();
+            for image_h in material.get_images().into_iter() {
+                if let Some(MipmapTask {
+                    material_handles,
+                    role,
+                    ..
+                }) = tasks.get_mut(image_h)
+                {
+                    warn_role_conflicts(image_h, *role, &roles, image_overrides);
+                    material_handles.push(Handle::Weak(*material_h));
+                    continue; //There is already a task for this image
+                }
+                if let Some(job) = jobs.iter_mut().find(|job| job.image_h == *image_h) {
+                    warn_role_conflicts(image_h, job.role, &roles, image_overrides);
+                    job.users.push(Handle::Weak(*material_h));
+                    continue;
+                }
+                if let Some(image) = images.get_mut(image_h) {
+                    let gpu_mipmapped = gpu_images
+                        .as_ref()
+                        .is_some_and(|gpu_images| gpu_images.contains(&image_h.id()));
+                    apply_sampler_settings(image, default_sampler, settings, gpu_mipmapped, None);
+                    // Images flagged for GPU generation get their mips from GpuMipmapGeneratorPlugin
+                    if gpu_mipmapped {
+                        continue;
+                    }
+                    if image.texture_descriptor.mip_level_count != 1 {
+                        debug!(
+                            "Skipping {:?}, it already has {} mip levels",
+                            image_h, image.texture_descriptor.mip_level_count
+                        );
+                        continue;
+                    }
+                    if let Err(e) = check_image_compatible(image, settings) {
+                        if settings.strict {
+                            report_strict_error(
+                                &mut events.errors,
+                                image_h.id(),
+                                e.to_string(),
+                                settings.strict_panic,
+                            );
+                        }
+                    } else {
+                        let mut image = image.clone();
+                        let role = roles
+                            .iter()
+                            .find(|(h, _)| *h == image_h)
+                            .map(|(_, role)| *role);
+                        let settings = resolve_image_settings(
+                            image_h,
+                            &mut image,
+                            settings,
+                            image_overrides,
+                            role,
+                        );
+                        let role = image_role(
+                            image_h,
+                            image.texture_descriptor.format,
+                            image_overrides,
+                            role,
+                        );
+                        jobs.push(MipmapJob {
+                            image_h: image_h.clone(),
+                            image,
+                            settings,
+                            role,
+                            users: vec![Handle::Weak(*material_h)],
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    settings
+        .processing_order
+        .sort(&mut jobs, |job| (job.image_h.id(), &job.image));
+    *backlog = split_off_backlog(&mut jobs, settings);
+    for job in jobs {
+        let (task, partial_images) = spawn_mipmap_task(job.image, job.settings);
+        events.started.send(MipmapGenerationStarted {
+            image: job.image_h.clone_weak(),
+        });
+        tasks.insert(
+            job.image_h,
+            MipmapTask {
+                task,
+                material_handles: job.users,
+                partial_images,
+                spawned_at: Instant::now(),
+                slow_warning_logged: false,
+                role: job.role,
+            },
+        );
+        progress.total += 1;
+    }
+}
+
+fn apply_material_tasks<M: Material + GetImages>(
+    settings: &MipmapGeneratorSettings,
+    tasks: &mut MipmapTasks<M>,
+    materials: &mut Assets<M>,
+    images: &mut Assets<Image>,
+    progress: &mut MipmapGenerationProgress,
+    processed: &mut ProcessedImages,
+    events: &mut MipmapEvents,
+) {
+    let mut applied = 0;
+    tasks.retain(|image_h, mipmap_task| {
+        if settings
+            .apply_budget_per_frame
+            .is_some_and(|budget| applied >= budget)
+        {
+            return true;
+        }
+        let MipmapTask {
+            task,
+            material_handles,
+            partial_images,
+            spawned_at,
+            slow_warning_logged,
+            ..
+        } = mipmap_task;
+        let poll = poll_mipmap_task(image_h, task, partial_images, images, progress, processed);
+        if let Some(error) = poll.error {
+            report_strict_error(
+                &mut events.errors,
+                image_h.id(),
+                error,
+                settings.strict_panic,
+            );
+        }
+        if !poll.finished {
+            warn_if_slow(image_h, *spawned_at, slow_warning_logged, settings);
+        }
+        if poll.updated {
+            // Touch material to trigger change detection
+            for material_h in material_handles.iter() {
+                let _ = materials.get_mut(material_h);
+            }
+        }
+        if poll.finished {
+            applied += 1;
+            events.generated.send(MipmapsGenerated {
+                image: image_h.clone_weak(),
+            });
+        }
+        !poll.finished
+    });
+}
+
+/// Images to generate mipmaps for that aren't used through a material, like UI images.
+/// Handles stay queued until the image is loaded, then `generate_image_mipmaps` processes them
+/// with the same settings, overrides and caching as material images.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MipmapImageQueue(pub Vec<MipmapImageRequest>);
+
+#[derive(Clone)]
+pub struct MipmapImageRequest {
+    pub image: Handle<Image>,
+    /// If set, replaces the address modes of images that use the default sampler.
+    pub address_mode: Option<ImageAddressMode>,
+    /// If set, used instead of the `MipmapGeneratorSettings` resource for this image.
+    pub settings: Option<MipmapGeneratorSettings>,
+}
+
+impl From<Handle<Image>> for MipmapImageRequest {
+    fn from(image: Handle<Image>) -> Self {
+        MipmapImageRequest {
+            image,
+            address_mode: None,
+            settings: None,
+        }
+    }
+}
+
+/// Requests mipmap generation for an image, independent of any material. The request is added to
+/// `MipmapImageQueue`, so it's processed once the image is loaded. Images that already have mips
+/// are skipped.
+#[derive(Event, Clone)]
+pub struct GenerateMipmaps {
+    pub image: Handle<Image>,
+    /// If set, used instead of the `MipmapGeneratorSettings` resource for this image.
+    pub settings: Option<MipmapGeneratorSettings>,
+}
+
+impl GenerateMipmaps {
+    pub fn new(image: Handle<Image>) -> Self {
+        GenerateMipmaps {
+            image,
+            settings: None,
+        }
+    }
+
+    pub fn with_settings(mut self, settings: MipmapGeneratorSettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+}
+
+/// Adds `GenerateMipmaps` events to `MipmapImageQueue`. Added by `MipmapGeneratorPlugin`.
+pub fn queue_requested_mipmaps(
+    mut requests: EventReader<GenerateMipmaps>,
+    mut queue: ResMut<MipmapImageQueue>,
+) {
+    for request in requests.read() {
+        queue.push(MipmapImageRequest {
+            image: request.image.clone(),
+            address_mode: None,
+            settings: request.settings.clone(),
+        });
+    }
+}
+
+pub struct MipmapImageTask {
+    pub task: Task<TaskData>,
+    pub partial_images: Option<Mutex<Receiver<Image>>>,
+    pub spawned_at: Instant,
+    slow_warning_logged: bool,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MipmapImageTasks(HashMap<Handle<Image>, MipmapImageTask>);
+
+impl MipmapImageTasks {
+    /// Number of tasks that are running or finished but not yet written back.
+    pub fn in_flight(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Generates mipmaps for the images in `MipmapImageQueue`. Added by `MipmapGeneratorPlugin`.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_image_mipmaps(
+    mut queue: ResMut<MipmapImageQueue>,
+    mut tasks: ResMut<MipmapImageTasks>,
+    mut images: ResMut<Assets<Image>>,
+    default_sampler: Res<DefaultSampler>,
+    mut progress: ResMut<MipmapGenerationProgress>,
+    mut processed: ResMut<ProcessedImages>,
+    settings: Res<MipmapGeneratorSettings>,
+    gpu_images: Option<Res<GpuMipmapImages>>,
+    image_overrides: Option<Res<MipmapImageOverrides>>,
+    mut events: MipmapEvents,
+    mut backlog: Local<Vec<MipmapJob<()>>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let tasks = &mut tasks.0;
+    if tasks.capacity() < settings.task_capacity {
+        tasks.reserve(settings.task_capacity - tasks.len());
+    }
+    let mut jobs = std::mem::take(&mut *backlog);
+    queue.retain(|request| {
+        let image_h = &request.image;
+        let settings = request.settings.as_ref().unwrap_or(&settings);
+        if tasks.contains_key(image_h) || jobs.iter().any(|job| job.image_h == *image_h) {
+            return false;
+        }
+        // Keep waiting until the image is loaded
+        let Some(image) = images.get(image_h) else {
+            return true;
+        };
+        // Check with get() first so already processed images don't trigger change detection
+        if image.texture_descriptor.mip_level_count != 1 {
+            debug!(
+                "Skipping {:?}, it already has {} mip levels",
+                image_h, image.texture_descriptor.mip_level_count
+            );
+            return false;
+        }
+        let Some(image) = images.get_mut(image_h) else {
+            return true;
+        };
+        let gpu_mipmapped = gpu_images
+            .as_ref()
+            .is_some_and(|gpu_images| gpu_images.contains(&image_h.id()));
+        apply_sampler_settings(
+            image,
+            &default_sampler,
+            settings,
+            gpu_mipmapped,
+            request.address_mode,
+        );
+        if gpu_mipmapped {
+            return false;
+        }
+        if let Err(e) = check_image_compatible(image, settings) {
+            if settings.strict {
+                report_strict_error(
+                    &mut events.errors,
+                    image_h.id(),
+                    e.to_string(),
+                    settings.strict_panic,
+                );
+            }
+        } else {
+            let mut image = image.clone();
+            let settings = resolve_image_settings(
+                image_h,
+                &mut image,
+                settings,
+                image_overrides.as_deref(),
+                None,
+            );
+            let role = image_role(
+                image_h,
+                image.texture_descriptor.format,
+                image_overrides.as_deref(),
+                None,
+            );
+            jobs.push(MipmapJob {
+                image_h: image_h.clone(),
+                image,
+                settings,
+                role,
+                users: (),
+            });
+        }
+        false
+    });
+
+    settings
+        .processing_order
+        .sort(&mut jobs, |job| (job.image_h.id(), &job.image));
+    *backlog = split_off_backlog(&mut jobs, &settings);
+    for job in jobs {
+        let (task, partial_images) = spawn_mipmap_task(job.image, job.settings);
+        events.started.send(MipmapGenerationStarted {
+            image: job.image_h.clone_weak(),
+        });
+        tasks.insert(
+            job.image_h,
+            MipmapImageTask {
+                task,
+                partial_images,
+                spawned_at: Instant::now(),
+                slow_warning_logged: false,
+            },
+        );
+        progress.total += 1;
+    }
+
+    let mut applied = 0;
+    tasks.retain(
+        |image_h,
+         MipmapImageTask {
+             task,
+             partial_images,
+             spawned_at,
+             slow_warning_logged,
+         }| {
+            if settings
+                .apply_budget_per_frame
+                .is_some_and(|budget| applied >= budget)
+            {
+                return true;
+            }
+            let poll = poll_mipmap_task(
+                image_h,
+                task,
+                partial_images,
+                &mut images,
+                &mut progress,
+                &mut processed,
+            );
+            if let Some(error) = poll.error {
+                report_strict_error(
+                    &mut events.errors,
+                    image_h.id(),
+                    error,
+                    settings.strict_panic,
+                );
+            }
+            if !poll.finished {
+                warn_if_slow(image_h, *spawned_at, slow_warning_logged, &settings);
+            }
+            if poll.finished {
+                applied += 1;
+                events.generated.send(MipmapsGenerated {
+                    image: image_h.clone_weak(),
+                });
+            }
+            !poll.finished
+        },
+    );
+}
+
+/// Queues the images of `UiImage` nodes in `MipmapImageQueue`. UI images are clamped to the edge
+/// since the lower mip levels would otherwise blend in the opposite side of the image.
+#[cfg(feature = "ui")]
+pub fn queue_ui_image_mipmaps(
+    ui_images: Query<&UiImage, Changed<UiImage>>,
+    mut queue: ResMut<MipmapImageQueue>,
+) {
+    for ui_image in &ui_images {
+        queue.push(MipmapImageRequest {
+            image: ui_image.texture.clone(),
+            address_mode: Some(ImageAddressMode::ClampToEdge),
+            settings: None,
+        });
+    }
+}
+
+/// Queues the images of `Sprite` entities in `MipmapImageQueue`, clamped to the edge like UI
+/// images. Sprites using a `TextureAtlas` are skipped, since the lower mip levels would blend
+/// neighboring cells of the atlas together.
+#[cfg(feature = "sprite")]
+#[allow(clippy::type_complexity)]
+pub fn queue_sprite_mipmaps(
+    sprites: Query<&Handle<Image>, (With<Sprite>, Without<TextureAtlas>, Changed<Handle<Image>>)>,
+    mut queue: ResMut<MipmapImageQueue>,
+) {
+    for image_h in &sprites {
+        queue.push(MipmapImageRequest {
+            image: image_h.clone(),
+            address_mode: Some(ImageAddressMode::ClampToEdge),
+            settings: None,
+        });
+    }
+}
+
+/// An image that a task will be spawned for, with its resolved settings.
+/// `users` are the materials to touch once the image is updated, if any.
+/// Public only because the generation systems keep jobs deferred by `max_new_tasks_per_frame`.
+pub struct MipmapJob<T> {
+    image_h: Handle<Image>,
+    image: Image,
+    settings: MipmapGeneratorSettings,
+    role: TextureRole,
+    users: T,
+}
+
+/// Sets the anisotropic filtering from `settings` according to `settings.anisotropy_policy` and
+/// the optional compare function and border color, materializing the default sampler if needed.
+/// Anisotropy is skipped for samplers that don't filter linearly, wgpu rejects that combination.
+fn apply_sampler_settings(
+    image: &mut Image,
+    default_sampler: &DefaultSampler,
+    settings: &MipmapGeneratorSettings,
+    gpu_mipmapped: bool,
+    default_address_mode: Option<ImageAddressMode>,
+) {
+    let apply_anisotropy = match settings.anisotropy_policy {
+        AnisotropyPolicy::AllImages => true,
+        AnisotropyPolicy::MipmappedOnly => {
+            gpu_mipmapped
+                || image.texture_descriptor.mip_level_count > 1
+                || check_image_compatible(image, settings).is_ok()
+        }
+        AnisotropyPolicy::None => false,
+    };
+    let size = image.texture_descriptor.size;
+    let apply_anisotropy =
+        apply_anisotropy && size.width.min(size.height) >= settings.anisotropy_min_resolution;
+    let mut descriptor = match image.sampler.clone() {
+        ImageSampler::Default => {
+            let mut descriptor = default_sampler.0.clone();
+            if let Some(address_mode) = default_address_mode {
+                descriptor.address_mode_u = address_mode;
+                descriptor.address_mode_v = address_mode;
+                descriptor.address_mode_w = address_mode;
+            }
+            descriptor
+        }
+        ImageSampler::Descriptor(descriptor) => descriptor,
+    };
+    let linear = [
+        descriptor.mag_filter,
+        descriptor.min_filter,
+        descriptor.mipmap_filter,
+    ]
+    .iter()
+    .all(|filter| matches!(filter, ImageFilterMode::Linear));
+    if apply_anisotropy && linear {
+        descriptor.anisotropy_clamp = settings.anisotropic_filtering.into();
+    }
+    if settings.sampler_compare.is_some() {
+        descriptor.compare = settings.sampler_compare;
+    }
+    if let Some(border_color) = settings.sampler_border_color {
+        let clamps_to_border = [
+            descriptor.address_mode_u,
+            descriptor.address_mode_v,
+            descriptor.address_mode_w,
+        ]
+        .iter()
+        .any(|mode| matches!(mode, ImageAddressMode::ClampToBorder));
+        if !clamps_to_border {
+            warn!(
+                "Border color {:?} is set but no address mode is ClampToBorder, it has no effect.",
+                border_color
+            );
+        }
+        descriptor.border_color = Some(border_color);
+    }
+    image.sampler = ImageSampler::Descriptor(descriptor);
+}
+
+/// Returns the settings to process this image with. Applies the color space override to `image`
+/// and picks the filter and sharpening from the image's role. `role` is what the material reports.
+fn resolve_image_settings(
+    image_h: &Handle<Image>,
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+    image_overrides: Option<&MipmapImageOverrides>,
+    role: Option<TextureRole>,
+) -> MipmapGeneratorSettings {
+    let mut settings = settings.clone();
+    let image_override = image_overrides.and_then(|overrides| overrides.get(&image_h.id()));
+    if let Some(minimum_mip_resolution) = image_override.and_then(|o| o.minimum_mip_resolution) {
+        settings.minimum_mip_resolution = minimum_mip_resolution;
+    }
+    if let Some(color_space) = image_override.and_then(|o| o.color_space) {
+        let format = image.texture_descriptor.format;
+        let new_format = color_space.apply_to_format(format);
+        if new_format != format {
+            info!(
+                "Overriding color space of {:?} to {:?}: {:?} -> {:?}",
+                image_h, color_space, format, new_format
+            );
+            image.texture_descriptor.format = new_format;
+        }
+    }
+    let format = image.texture_descriptor.format;
+    let role = image_role(image_h, format, image_overrides, role);
+    if settings.use_recommended_filters {
+        settings.filter_type = recommended_filter(format, role);
+    }
+    // Sharpening and grading would skew normals and packed data
+    if role != TextureRole::Color {
+        settings.post_downsample_sharpen = None;
+        settings.color_grade = None;
+        settings.downsample_gamma = GammaMode::Linear;
+    }
+    if role != TextureRole::Normal {
+        settings.renormalize_normals = false;
+    }
+    settings
+}
+
+/// The role an image is processed in: the override, then the role reported by the material, then
+/// the fallback from the format.
+fn image_role(
+    image_h: &Handle<Image>,
+    format: TextureFormat,
+    image_overrides: Option<&MipmapImageOverrides>,
+    role: Option<TextureRole>,
+) -> TextureRole {
+    image_overrides
+        .and_then(|overrides| overrides.get(&image_h.id()))
+        .and_then(|o| o.role)
+        .or(role)
+        .unwrap_or_else(|| TextureRole::from_format(format))
+}
+
+/// Warns about the roles in `material_roles` that `image_h` is used in besides `processed_role`,
+/// the role it's already being processed in. Images with an overridden role are skipped.
+fn warn_role_conflicts(
+    image_h: &Handle<Image>,
+    processed_role: TextureRole,
+    material_roles: &[(&Handle<Image>, TextureRole)],
+    image_overrides: Option<&MipmapImageOverrides>,
+) {
+    if image_overrides
+        .and_then(|overrides| overrides.get(&image_h.id()))
+        .is_some_and(|o| o.role.is_some())
+    {
+        return;
+    }
+    for (_, role) in material_roles.iter().filter(|(h, _)| *h == image_h) {
+        if *role != processed_role {
+            warn!(
+                "{:?} is used as {:?} and {:?}, it's only processed as {:?}. Use separate images \
+                or set MipmapImageOverride::role.",
+                image_h, processed_role, role, processed_role
+            );
+        }
+    }
+}
+
+/// Removes the jobs over `max_new_tasks_per_frame` from `jobs`, to be spawned in a later frame.
+fn split_off_backlog<T>(
+    jobs: &mut Vec<MipmapJob<T>>,
+    settings: &MipmapGeneratorSettings,
+) -> Vec<MipmapJob<T>> {
+    match settings.max_new_tasks_per_frame {
+        Some(max) if jobs.len() > max => jobs.split_off(max),
+        _ => Vec::new(),
+    }
+}
+
+fn spawn_mipmap_task(
+    mut image: Image,
+    settings: MipmapGeneratorSettings,
+) -> (Task<TaskData>, Option<Mutex<Receiver<Image>>>) {
+    let mut added_cache_size = 0;
+    let (sender, receiver) = channel();
+    let progressive = settings.progressive;
+    let source = settings.keep_source_images.then(|| image.clone());
+    let task = async_compute_task_pool().spawn(async move {
+        let result = if settings.progressive {
+            generate_mips_texture_progressive(
+                &mut image,
+                &settings,
+                &mut added_cache_size,
+                |partial| {
+                    let _ = sender.send(partial);
+                },
+            )
+        } else {
+            generate_mips_texture(&mut image, &settings, &mut added_cache_size)
+        };
+        let error = match result {
+            Err(e) if settings.strict => Some(e.to_string()),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+            Ok(()) => None,
+        };
+        TaskData {
+            added_cache_size,
+            image,
+            source,
+            error,
+        }
+    });
+    (task, progressive.then(|| Mutex::new(receiver)))
+}
+
+/// Minimal Apps without `TaskPoolPlugin` don't set up the task pools, fall back to a default
+/// pool instead of panicking.
+fn async_compute_task_pool() -> &'static AsyncComputeTaskPool {
+    AsyncComputeTaskPool::try_get().unwrap_or_else(|| {
+        warn!("AsyncComputeTaskPool is not initialized, creating a default one for mipmap generation. Add TaskPoolPlugin to configure it.");
+        AsyncComputeTaskPool::get_or_init(TaskPool::default)
+    })
+}
+
+struct TaskPoll {
+    /// The task is done and can be removed.
+    finished: bool,
+    /// The image was replaced with a partial or final result.
+    updated: bool,
+    /// Error to report for `MipmapGeneratorSettings::strict`.
+    error: Option<String>,
+}
+
+fn poll_mipmap_task(
+    image_h: &Handle<Image>,
+    task: &mut Task<TaskData>,
+    partial_images: &Option<Mutex<Receiver<Image>>>,
+    images: &mut Assets<Image>,
+    progress: &mut MipmapGenerationProgress,
+    processed: &mut ProcessedImages,
+) -> TaskPoll {
+    fn bytes_to_gb(bytes: usize) -> usize {
+        bytes / 1024_usize.pow(3)
+    }
+
+    let mut updated = false;
+    // Only the most recent partial result is worth uploading
+    if let Some(partial) = partial_images
+        .as_ref()
+        .and_then(|receiver| receiver.lock().ok()?.try_iter().last())
+    {
+        if let Some(image) = images.get_mut(image_h) {
+            *image = partial;
+            updated = true;
+        }
+    }
+    let Some(task_data) = future::block_on(future::poll_once(task)) else {
+        return TaskPoll {
+            finished: false,
+            updated,
+            error: None,
+        };
+    };
+    if let Some(image) = images.get_mut(image_h) {
+        *image = task_data.image;
+        updated = true;
+        processed.insert(
+            image_h.clone_weak(),
+            ProcessedInfo {
+                format: image.texture_descriptor.format,
+                mip_level_count: image.texture_descriptor.mip_level_count,
+                size_bytes: image.data.len(),
+                source: task_data.source,
+            },
+        );
+        progress.processed += 1;
+        let prev_cached_data_gb = bytes_to_gb(progress.cached_data_size_bytes);
+        progress.cached_data_size_bytes += task_data.added_cache_size;
+        let current_cached_data_gb = bytes_to_gb(progress.cached_data_size_bytes);
+        if current_cached_data_gb > prev_cached_data_gb {
+            warn!(
+                "Generated cached texture data from just this run is {}",
+                format_bytes_size(progress.cached_data_size_bytes)
+            );
+        }
+    }
+    TaskPoll {
+        finished: true,
+        updated,
+        error: task_data.error,
+    }
+}
+
+/// See `MipmapGeneratorSettings::slow_task_warning`.
+fn warn_if_slow(
+    image_h: &Handle<Image>,
+    spawned_at: Instant,
+    slow_warning_logged: &mut bool,
+    settings: &MipmapGeneratorSettings,
+) {
+    let Some(slow_task_warning) = settings.slow_task_warning else {
+        return;
+    };
+    if !*slow_warning_logged && spawned_at.elapsed() >= slow_task_warning {
+        *slow_warning_logged = true;
+        warn!(
+            "Mipmap generation for {:?} is still running after {:.1}s",
+            image_h,
+            spawned_at.elapsed().as_secs_f32()
+        );
+    }
+}
+
+/// Sent when a generation task is spawned for an image. The handle is weak.
+#[derive(Event, Clone, Debug)]
+pub struct MipmapGenerationStarted {
+    pub image: Handle<Image>,
+}
+
+/// Sent when the generation task of an image has finished and its result was written back, also
+/// when the generation failed. Pairs with `MipmapGenerationStarted`, for example to show a loading
+/// screen until every started image is done. The handle is weak.
+#[derive(Event, Clone, Debug)]
+pub struct MipmapsGenerated {
+    pub image: Handle<Image>,
+}
+
+/// The events sent by the generation systems.
+#[derive(SystemParam)]
+pub struct MipmapEvents<'w> {
+    errors: EventWriter<'w, MipmapGenerationError>,
+    started: EventWriter<'w, MipmapGenerationStarted>,
+    generated: EventWriter<'w, MipmapsGenerated>,
+}
+
+/// Sent for images that couldn't be processed when `MipmapGeneratorSettings::strict` is enabled.
+#[derive(Event, Clone, Debug)]
+pub struct MipmapGenerationError {
+    pub image: AssetId<Image>,
+    pub error: String,
+}
+
+/// Logs and sends a `MipmapGenerationError`, then panics if `strict_panic` is set, see
+/// `MipmapGeneratorSettings::strict_panic`.
+fn report_strict_error(
+    errors: &mut EventWriter<MipmapGenerationError>,
+    image: AssetId<Image>,
+    error: String,
+    strict_panic: bool,
+) {
+    error!("Mipmap generation failed for {:?}: {}", image, error);
+    errors.send(MipmapGenerationError {
+        image,
+        error: error.clone(),
+    });
+    if strict_panic {
+        panic!(
+            "Mipmap generation failed for {:?} with strict enabled: {}",
+            image, error
+        );
+    }
+}
+
+/// `added_cache_size` is for tracking the amount of data that was cached by this call.
+/// Compressed BCn data is cached on disk if cache_compressed_image_data is enabled.
+/// No more levels are generated than the image's sampler can access, see `sampler_max_mip_count`.
+/// The layers of 2D arrays are processed one by one, and their chains are stored one after the
+/// other (all levels of layer 0 first), the order bevy uploads image data in.
+pub fn generate_mips_texture(
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+    added_cache_size: &mut usize,
+) -> Result<(), MipmapError> {
+    generate_mips_texture_inner(image, settings, added_cache_size, None)
+}
+
+/// Generates the mip chain of an image that isn't used by a material, like a texture created at
+/// runtime. Same as `generate_mips_texture` without the cache size tracking.
+/// Runs synchronously on the calling thread, send a `GenerateMipmaps` event instead to generate
+/// the chain of an image in `Assets<Image>` on the task pool.
+pub fn generate_mipmaps_for_image(
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+) -> Result<(), MipmapError> {
+    generate_mips_texture(image, settings, &mut 0)
+}
+
+/// Same as `generate_mips_texture`, but `on_partial` is called with partial images while the chain
+/// is being generated, starting with only the smallest levels. Each partial image has the full
+/// size and level count, with the missing larger levels zeroed and excluded through the texture
+/// view's `base_mip_level`, so the sampler is left as it is. Images loaded from the cache are
+/// returned without partial results.
+pub fn generate_mips_texture_progressive(
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+    added_cache_size: &mut usize,
+    mut on_partial: impl FnMut(Image),
+) -> Result<(), MipmapError> {
+    generate_mips_texture_inner(image, settings, added_cache_size, Some(&mut on_partial))
+}
+
+/// wgpu textures can't mix formats within one mip chain, so this produces the chain as two images
+/// instead: levels `0..compressed_from_level` uncompressed, and the remaining levels compressed
+/// according to `settings.compression`, with level `compressed_from_level` as the tail's mip 0.
+/// Use this to keep the largest levels at full quality while still saving memory on the rest.
+/// Both images need to be bound, with the shader sampling the tail at `lod - compressed_from_level`
+/// once the lod reaches `compressed_from_level`. With a `compressed_from_level` of 1 a material can
+/// instead use the uncompressed mip 0 up close and swap to the tail at a distance, see
+/// `mip_level_distance`.
+/// The tail is generated from level `compressed_from_level`, so alpha coverage is preserved
+/// relative to that level and the tail stops at the 4x4 limit of BCn compression.
+pub fn generate_mips_texture_split(
+    image: &Image,
+    settings: &MipmapGeneratorSettings,
+    compressed_from_level: u32,
+    added_cache_size: &mut usize,
+) -> Result<(Image, Image), MipmapError> {
+    let mut uncompressed = image.clone();
+    let uncompressed_settings = MipmapGeneratorSettings {
+        compression: None,
+        ..settings.clone()
+    };
+    generate_mips_texture(&mut uncompressed, &uncompressed_settings, added_cache_size)?;
+
+    let mip_count = uncompressed.texture_descriptor.mip_level_count;
+    if compressed_from_level == 0 || compressed_from_level >= mip_count {
+        return Err(MipmapError::SplitLevelOutOfRange {
+            level: compressed_from_level,
+            mip_level_count: mip_count,
+        });
+    }
+
+    let mut tail = extract_mip_range(&uncompressed, compressed_from_level, 1)?;
+    generate_mips_texture(&mut tail, settings, added_cache_size)?;
+    let head = extract_mip_range(&uncompressed, 0, compressed_from_level)?;
+    Ok((head, tail))
+}
+
+/// Distance from the camera at which the GPU starts sampling mip `level` of a texture that's
+/// `texture_size` texels wide and covers `world_size` units of a surface facing the camera, for a
+/// perspective camera with a vertical `fov` in radians rendering `viewport_height` pixels.
+///
+/// With `generate_mips_texture_split` and a `compressed_from_level` of 1, this gives a distance to
+/// swap the material from the uncompressed mip 0 to the compressed tail without visible loss:
+/// beyond `mip_level_distance(.., 1)` mip 0 isn't sampled anyway. Surfaces seen at an angle use
+/// lower levels sooner, so this is a conservative distance.
+pub fn mip_level_distance(
+    texture_size: u32,
+    world_size: f32,
+    fov: f32,
+    viewport_height: f32,
+    level: u32,
+) -> f32 {
+    // At distance d a pixel covers 2 * d * tan(fov / 2) / viewport_height units, and level n is
+    // used once a pixel covers 2^n texels.
+    let texels_per_unit = texture_size as f32 / world_size;
+    let pixels_per_unit_at_one = viewport_height / (2.0 * (fov * 0.5).tan());
+    2f32.powi(level as i32) * pixels_per_unit_at_one / texels_per_unit
+}
+
+/// Same as `generate_mips_texture` for tightly packed pixel data without an `Image`. Returns the
+/// data of the whole mip chain and its mip level count. The data uses the format
+/// `generate_mips_texture` would give the image, so with compression enabled it's the matching
+/// BCn format from `compressible_formats` (or `force_output_format`). The default sampler is
+/// assumed, so the chain isn't limited by `sampler_max_mip_count`.
+pub fn generate_mips_raw(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    settings: &MipmapGeneratorSettings,
+) -> Result<(Vec<u8>, u32), MipmapError> {
+    let mut image = Image {
+        data: data.to_vec(),
+        ..default()
+    };
+    image.texture_descriptor.size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    image.texture_descriptor.format = format;
+    generate_mips_texture(&mut image, settings, &mut 0)?;
+    Ok((image.data, image.texture_descriptor.mip_level_count))
+}
+
+fn generate_mips_texture_inner(
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+    #[allow(unused)] added_cache_size: &mut usize,
+    on_partial: Option<&mut dyn FnMut(Image)>,
+) -> Result<(), MipmapError> {
+    if let Some(descriptor_hook) = &settings.descriptor_hook {
+        descriptor_hook(&mut image.texture_descriptor);
+    }
+    check_image_compatible(image, settings)?;
+    if image.texture_descriptor.size.depth_or_array_layers > 1 {
+        return generate_mips_array(image, settings, added_cache_size);
+    }
+    match try_into_dynamic(image.clone()) {
+        Ok(mut dyn_image) => {
+            let (width, height) = scaled_base_size(dyn_image.width(), dyn_image.height(), settings);
+            if (width, height) != (dyn_image.width(), dyn_image.height()) {
+                let color_space = ColorSpace::of_descriptor(&image.texture_descriptor);
+                dyn_image = downsample(&dyn_image, color_space, width, height, settings);
+                image.texture_descriptor.size.width = width;
+                image.texture_descriptor.size.height = height;
+            }
+
+            let forced_settings;
+            let settings = match settings.force_output_format {
+                Some(format) => {
+                    forced_settings = forced_output_settings(
+                        format,
+                        &dyn_image,
+                        image.texture_descriptor.format,
+                        settings,
+                    )?;
+                    &forced_settings
+                }
+                None => settings,
+            };
+            // Only Rgba8 images can be stored in the chosen format, the rest keep the inferred one
+            let format_settings;
+            let settings = match settings.compression_format {
+                CompressionFormat::Auto => settings,
+                _ if settings.force_output_format.is_some()
+                    || !matches!(dyn_image, DynamicImage::ImageRgba8(_)) =>
+                {
+                    settings
+                }
+                compression_format => {
+                    let mut resolved = settings.clone();
+                    compression_format.apply_to(&mut resolved);
+                    format_settings = resolved;
+                    &format_settings
+                }
+            };
+            let sized_settings;
+            let settings = match (&settings.compression_speed_for_size, settings.compression) {
+                (Some(speed_for_size), Some(_)) => {
+                    sized_settings = MipmapGeneratorSettings {
+                        compression: Some(speed_for_size(dyn_image.width(), dyn_image.height())),
+                        ..settings.clone()
+                    };
+                    &sized_settings
+                }
+                _ => settings,
+            };
+
+            #[allow(unused_mut)]
+            let mut has_alpha = false;
+            #[cfg(feature = "compress")]
+            if let Some(img) = dyn_image.as_rgba8() {
+                has_alpha = !settings.detect_opaque_alpha || img.pixels().any(|px| px.0[3] != 255);
+            }
+
+            #[cfg(feature = "compress")]
+            let mut compressed_format = None;
+            #[cfg(feature = "compress")]
+            let mut fallback_format = None;
+            #[allow(unused_mut)]
+            let mut compression_speed = settings.compression;
+            #[cfg(feature = "compress")]
+            {
+                if let Some(encoder_setting) = settings.compression {
+                    if let Some(format) = settings.force_output_format {
+                        compressed_format = Some(format);
+                        // BC1 is only picked by the encoder for opaque images
+                        if format.remove_srgb_suffix() == TextureFormat::Bc1RgbaUnorm {
+                            has_alpha = false;
+                        }
+                    } else {
+                        // BC1 is only picked by the encoder for opaque images
+                        if settings.compression_format == CompressionFormat::Bc1
+                            && matches!(dyn_image, DynamicImage::ImageRgba8(_))
+                        {
+                            has_alpha = false;
+                        }
+                        if settings.detect_grayscale && !image.texture_descriptor.format.is_srgb() {
+                            if let Some(gray) = grayscale_of_opaque_rgba(&dyn_image) {
+                                dyn_image = gray;
+                                has_alpha = false;
+                            }
+                        }
+                        compressed_format = bcn_equivalent_format_of_dyn_image(
+                            &dyn_image,
+                            image.texture_descriptor.format.is_srgb(),
+                            settings.low_quality,
+                            has_alpha,
+                        )
+                        .ok();
+                        if compressed_format.is_none() {
+                            fallback_format = settings
+                                .compression_fallback
+                                .format_for(image.texture_descriptor.format);
+                            if fallback_format.is_some() {
+                                dyn_image = DynamicImage::ImageRgba8(dyn_image.to_rgba8());
+                            }
+                        }
+                        if settings.use_bc3_for_rgba && !settings.low_quality {
+                            compressed_format = compressed_format.map(bc7_to_bc3);
+                        }
+                        if let (Some(format), Some(color_space)) = (
+                            compressed_format.filter(|f| *f != TextureFormat::Bc6hRgbUfloat),
+                            settings.compressed_color_space,
+                        ) {
+                            match bcn_format_with_color_space(format, color_space) {
+                                Ok(format) => compressed_format = Some(format),
+                                Err(e) => warn!("{}", e),
+                            }
+                        }
+                    }
+                    compression_speed = compressed_format.map(|_| encoder_setting);
+                }
+            }
+
+            let mip_count = calculate_mip_count(
+                dyn_image.width(),
+                dyn_image.height(),
+                settings.minimum_mip_resolution,
+                max_mip_count(image, settings),
+                compression_speed,
+            );
+
+            #[cfg(feature = "compress")]
+            let mut input_hash = u64::MAX;
+            #[allow(unused_mut)]
+            let mut loaded_from_cache = false;
+            let mut new_image_data = Vec::new();
+
+            #[cfg(feature = "compress")]
+            if compression_speed.is_some() && compressed_format.is_some() {
+                let cache_path = settings.compressed_image_data_cache_path.as_ref();
+                if cache_path.is_some() || settings.memory_cache.is_some() {
+                    input_hash = calculate_hash(image, settings);
+                }
+                if let Some(compressed_image_data) = settings
+                    .memory_cache
+                    .as_ref()
+                    .and_then(|memory_cache| memory_cache.get(input_hash))
+                {
+                    new_image_data = compressed_image_data;
+                    loaded_from_cache = true;
+                } else if let (Some(cache_path), Some(format)) = (cache_path, compressed_format) {
+                    let expected_len = mip_chain_byte_size(
+                        dyn_image.width(),
+                        dyn_image.height(),
+                        format,
+                        mip_count,
+                    );
+                    if let Some(compressed_image_data) =
+                        load_from_cache(input_hash, cache_path, Some(expected_len))
+                    {
+                        if let Some(memory_cache) = &settings.memory_cache {
+                            memory_cache.insert(input_hash, &compressed_image_data);
+                        }
+                        new_image_data = compressed_image_data;
+                        loaded_from_cache = true;
+                    }
+                }
+            }
+
+            // Rgba16Float is processed as Rgba32F and Bgra8 as Rgba8, they're converted back if
+            // they stay uncompressed
+            #[cfg(feature = "compress")]
+            let stays_uncompressed = compressed_format.or(fallback_format).is_none();
+            #[cfg(not(feature = "compress"))]
+            let stays_uncompressed = true;
+            let half_float_output =
+                stays_uncompressed && image.texture_descriptor.format == TextureFormat::Rgba16Float;
+            let bgra_output = stays_uncompressed
+                && image.texture_descriptor.format.remove_srgb_suffix()
+                    == TextureFormat::Bgra8Unorm;
+
+            if !loaded_from_cache {
+                // The DynamicImage doesn't know if its data is sRGB, so pass it along
+                let color_space = ColorSpace::of_descriptor(&image.texture_descriptor);
+                if let Some(color_grade) = &settings.color_grade {
+                    if color_space == ColorSpace::Srgb {
+                        color_grade.apply(&mut dyn_image);
+                    }
+                }
+                new_image_data = if let Some(on_partial) = on_partial {
+                    #[cfg(feature = "compress")]
+                    let output_format = compressed_format
+                        .or(fallback_format)
+                        .unwrap_or(image.texture_descriptor.format);
+                    #[cfg(not(feature = "compress"))]
+                    let output_format = image.texture_descriptor.format;
+                    generate_mips_progressive(
+                        &mut dyn_image,
+                        color_space,
+                        has_alpha,
+                        mip_count,
+                        settings,
+                        |first_level, tail| {
+                            let converted_tail;
+                            let tail = if half_float_output {
+                                converted_tail = f32_data_to_f16(tail);
+                                &converted_tail
+                            } else if bgra_output {
+                                converted_tail = swap_red_blue(tail.to_vec());
+                                &converted_tail
+                            } else {
+                                tail
+                            };
+                            on_partial(partial_mip_image(
+                                image,
+                                output_format,
+                                mip_count,
+                                first_level,
+                                tail,
+                            ))
+                        },
+                    )
+                } else {
+                    generate_mips(&mut dyn_image, color_space, has_alpha, mip_count, settings)
+                };
+                if half_float_output {
+                    new_image_data = f32_data_to_f16(&new_image_data);
+                } else if bgra_output {
+                    new_image_data = swap_red_blue(new_image_data);
+                }
+                #[cfg(feature = "compress")]
+                if compression_speed.is_some() && compressed_format.is_some() {
+                    if let Some(memory_cache) = &settings.memory_cache {
+                        memory_cache.insert(input_hash, &new_image_data);
+                    }
+                    if let Some(cache_path) = &settings.compressed_image_data_cache_path {
+                        *added_cache_size += new_image_data.len();
+                        // The generated data is used either way, a failed write only costs the
+                        // cache entry
+                        if let Err(e) = save_to_cache(
+                            input_hash,
+                            &new_image_data,
+                            cache_path,
+                            settings.cache_max_bytes,
+                        ) {
+                            warn!(
+                                "Failed to write mipmap cache entry in {:?}: {}",
+                                cache_path, e
+                            );
+                        }
+                    }
+                }
+            }
+
+            image.texture_descriptor.mip_level_count = mip_count;
+            #[cfg(feature = "compress")]
+            if let Some(format) = compressed_format.or(fallback_format) {
+                image.texture_descriptor.format = format;
+                // Remove view formats for compressed and converted textures.
+                // TODO Is this an issue? A bit difficult to work around since it's &['static]
+                image.texture_descriptor.view_formats = &[];
+            }
+
+            image.data = new_image_data;
+            if let Some(asset_usage) = settings.asset_usage {
+                image.asset_usage = asset_usage;
+            }
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Generates the mips of each layer of a 2D array on its own, and stores the chains one layer
+/// after the other (all levels of layer 0, then all levels of layer 1, ...), the layer major
+/// order bevy uploads image data in. Compressed layers are cached one by one, or as a single
+/// entry for the whole array, see `MipmapGeneratorSettings::per_layer_cache`. If the layers end up
+/// in different formats because of `detect_grayscale` or `detect_opaque_alpha`, they are
+/// processed again without those so the array has a single format. No partial results are
+/// produced for arrays.
+/// Cubemaps (with `MipmapGeneratorSettings::cubemap_seamless`) are processed the same way, each
+/// face gets its own chain. Faces are filtered without looking across their edges for now.
+fn generate_mips_array(
+    image: &mut Image,
+    settings: &MipmapGeneratorSettings,
+    added_cache_size: &mut usize,
+) -> Result<(), MipmapError> {
+    let descriptor = &image.texture_descriptor;
+    let layer_count = descriptor.size.depth_or_array_layers as usize;
+    let layer_size = mip_chain_byte_size(
+        descriptor.size.width,
+        descriptor.size.height,
+        descriptor.format,
+        1,
+    );
+    if layer_size == 0 || image.data.len() < layer_size * layer_count {
+        return Err(MipmapError::InvalidData(descriptor.format));
+    }
+    let layer_images = image
+        .data
+        .chunks_exact(layer_size)
+        .take(layer_count)
+        .map(|data| {
+            let mut texture_descriptor = image.texture_descriptor.clone();
+            texture_descriptor.size.depth_or_array_layers = 1;
+            Image {
+                data: data.to_vec(),
+                texture_descriptor,
+                sampler: image.sampler.clone(),
+                texture_view_descriptor: None,
+                asset_usage: image.asset_usage,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Key of the entry for the whole array, without per_layer_cache
+    #[cfg(feature = "compress")]
+    let array_hash = (!settings.per_layer_cache
+        && settings.compression.is_some()
+        && (settings.compressed_image_data_cache_path.is_some()
+            || settings.memory_cache.is_some()))
+    .then(|| calculate_hash(image, settings));
+    #[cfg(feature = "compress")]
+    if let Some(cached) = array_hash.and_then(|hash| load_array_from_cache(hash, image, settings)) {
+        image.texture_descriptor.size = cached.texture_descriptor.size;
+        image.texture_descriptor.format = cached.texture_descriptor.format;
+        image.texture_descriptor.mip_level_count = cached.texture_descriptor.mip_level_count;
+        image.texture_descriptor.view_formats = &[];
+        image.data = cached.data;
+        if let Some(asset_usage) = settings.asset_usage {
+            image.asset_usage = asset_usage;
+        }
+        return Ok(());
+    }
+
+    let generate_layers = |settings: &MipmapGeneratorSettings, added_cache_size: &mut usize| {
+        // The hook already ran for the whole array
+        #[allow(unused_mut)]
+        let mut settings = MipmapGeneratorSettings {
+            descriptor_hook: None,
+            ..settings.clone()
+        };
+        // The whole array is cached below instead
+        #[cfg(feature = "compress")]
+        if array_hash.is_some() {
+            settings.compressed_image_data_cache_path = None;
+            settings.memory_cache = None;
+        }
+        layer_images
+            .iter()
+            .map(|layer| {
+                let mut layer = layer.clone();
+                generate_mips_texture_inner(&mut layer, &settings, added_cache_size, None)?;
+                Ok(layer)
+            })
+            .collect::<Result<Vec<_>, MipmapError>>()
+    };
+    let same_layout = |layers: &[Image]| {
+        layers.windows(2).all(|pair| {
+            let (a, b) = (&pair[0].texture_descriptor, &pair[1].texture_descriptor);
+            a.format == b.format && a.mip_level_count == b.mip_level_count
+        })
+    };
+    let mut layers = generate_layers(settings, added_cache_size)?;
+    if !same_layout(&layers) {
+        let consistent_settings = MipmapGeneratorSettings {
+            detect_grayscale: false,
+            detect_opaque_alpha: false,
+            ..settings.clone()
+        };
+        layers = generate_layers(&consistent_settings, added_cache_size)?;
+        if !same_layout(&layers) {
+            return Err(MipmapError::ArrayLayoutMismatch);
+        }
+    }
+
+    let layer_count = layer_count as u32;
+    image.texture_descriptor = layers[0].texture_descriptor.clone();
+    image.texture_descriptor.size.depth_or_array_layers = layer_count;
+    image.data = layers.into_iter().flat_map(|layer| layer.data).collect();
+    #[cfg(feature = "compress")]
+    if let Some(hash) = array_hash.filter(|_| image.is_compressed()) {
+        save_array_to_cache(hash, image, settings, added_cache_size);
+    }
+    if let Some(asset_usage) = settings.asset_usage {
+        image.asset_usage = asset_usage;
+    }
+    Ok(())
+}
+
+/// Loads the entry `save_array_to_cache` wrote for `image`, see
+/// `MipmapGeneratorSettings::per_layer_cache`. Entries that don't match the size of the array
+/// after `MipmapGeneratorSettings::base_scale` are treated as a miss.
+#[cfg(feature = "compress")]
+fn load_array_from_cache(
+    hash: u64,
+    image: &Image,
+    settings: &MipmapGeneratorSettings,
+) -> Option<Image> {
+    let memory_cache = settings.memory_cache.as_ref();
+    let (blob, from_disk) = match memory_cache.and_then(|memory_cache| memory_cache.get(hash)) {
+        Some(blob) => (blob, false),
+        None => (
+            load_from_cache(
+                hash,
+                settings.compressed_image_data_cache_path.as_ref()?,
+                None,
+            )?,
+            true,
+        ),
+    };
+    let cached = match from_blob(&blob) {
+        Ok(cached) => cached,
+        Err(e) => {
+            warn!("Ignoring corrupt mipmap cache entry {:x}: {}", hash, e);
+            return None;
+        }
+    };
+    let descriptor = &cached.texture_descriptor;
+    let expected_len = mip_chain_byte_size(
+        descriptor.size.width,
+        descriptor.size.height,
+        descriptor.format,
+        descriptor.mip_level_count,
+    ) * descriptor.size.depth_or_array_layers as usize;
+    let size = image.texture_descriptor.size;
+    let (width, height) = scaled_base_size(size.width, size.height, settings);
+    let expected_size = Extent3d {
+        width,
+        height,
+        ..size
+    };
+    if descriptor.size != expected_size || cached.data.len() != expected_len {
+        warn!(
+            "Ignoring mipmap cache entry {:x}, it doesn't match the image",
+            hash
+        );
+        return None;
+    }
+    if let (true, Some(memory_cache)) = (from_disk, memory_cache) {
+        memory_cache.insert(hash, &blob);
+    }
+    Some(cached)
+}
+
+/// Caches a processed array as a single entry. The entry is a blob (see `to_blob`), since the
+/// format and mip count have to be known without processing the layers again.
+#[cfg(feature = "compress")]
+fn save_array_to_cache(
+    hash: u64,
+    image: &Image,
+    settings: &MipmapGeneratorSettings,
+    added_cache_size: &mut usize,
+) {
+    let blob = match to_blob(image) {
+        Ok(blob) => blob,
+        Err(e) => {
+            warn!(
+                "Can't cache {:?} array: {}",
+                image.texture_descriptor.format, e
+            );
+            return;
+        }
+    };
+    if let Some(memory_cache) = &settings.memory_cache {
+        memory_cache.insert(hash, &blob);
+    }
+    if let Some(cache_path) = &settings.compressed_image_data_cache_path {
+        *added_cache_size += blob.len();
+        if let Err(e) = save_to_cache(hash, &blob, cache_path, settings.cache_max_bytes) {
+            warn!(
+                "Failed to write mipmap cache entry in {:?}: {}",
+                cache_path, e
+            );
+        }
+    }
+}
+
+/// Returns the red channel of an Rgba8 image as Luma8 if the image is opaque and red, green and
+/// blue are equal everywhere, see `MipmapGeneratorSettings::detect_grayscale`.
+#[cfg(feature = "compress")]
+fn grayscale_of_opaque_rgba(dyn_image: &DynamicImage) -> Option<DynamicImage> {
+    let rgba = dyn_image.as_rgba8()?;
+    if !rgba
+        .pixels()
+        .all(|px| px.0[0] == px.0[1] && px.0[0] == px.0[2] && px.0[3] == 255)
+    {
+        return None;
+    }
+    let red = rgba.pixels().map(|px| px.0[0]).collect();
+    Some(dyn_image_from_u8(rgba.width(), rgba.height(), 1, red))
+}
+
+/// Size of mip 0 after applying `MipmapGeneratorSettings::base_scale`.
+fn scaled_base_size(width: u32, height: u32, settings: &MipmapGeneratorSettings) -> (u32, u32) {
+    let min = if cfg!(feature = "compress") && settings.compression.is_some() {
+        4
+    } else {
+        1
+    };
+    let mut shift = settings.base_scale.max(1).ilog2();
+    while shift > 0 && ((width >> shift) < min || (height >> shift) < min) {
+        shift -= 1;
+    }
+    (width >> shift, height >> shift)
+}
+
+/// Adjusts `settings` so the encoder produces `format` for `dyn_image`, or errors if it can't,
+/// see `MipmapGeneratorSettings::force_output_format`. `compression` is `None` in the result if
+/// the image is left uncompressed.
+fn forced_output_settings(
+    format: TextureFormat,
+    dyn_image: &DynamicImage,
+    source_format: TextureFormat,
+    settings: &MipmapGeneratorSettings,
+) -> Result<MipmapGeneratorSettings, MipmapError> {
+    let mut forced = settings.clone();
+    forced.compressed_color_space = None;
+    forced.compression_fallback = CompressionFallback::KeepFormat;
+    if format == source_format && !format.is_compressed() {
+        forced.compression = None;
+        forced.force_output_format = None;
+        return Ok(forced);
+    }
+    if !cfg!(feature = "compress") {
+        return Err(MipmapError::CompressFeatureDisabled(format));
+    }
+    if dyn_image.width() < 4 || dyn_image.height() < 4 {
+        return Err(MipmapError::TooSmallForCompression);
+    }
+    let compatible = match format.remove_srgb_suffix() {
+        TextureFormat::Bc4RUnorm | TextureFormat::Bc5RgUnorm => {
+            forced.low_quality = false;
+            matches!(
+                (format, dyn_image),
+                (TextureFormat::Bc4RUnorm, DynamicImage::ImageLuma8(_))
+                    | (TextureFormat::Bc5RgUnorm, DynamicImage::ImageLumaA8(_))
+            )
+        }
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc7RgbaUnorm => {
+            forced.low_quality = false;
+            forced.use_bc3_for_rgba = format.remove_srgb_suffix() == TextureFormat::Bc3RgbaUnorm;
+            matches!(dyn_image, DynamicImage::ImageRgba8(_))
+        }
+        TextureFormat::Bc1RgbaUnorm => {
+            forced.low_quality = true;
+            matches!(
+                dyn_image,
+                DynamicImage::ImageRgba8(_) | DynamicImage::ImageLumaA8(_)
+            )
+        }
+        _ => false,
+    };
+    if !compatible {
+        return Err(MipmapError::ForcedFormatIncompatible {
+            source: source_format,
+            forced: format,
+        });
+    }
+    forced.compression = Some(settings.compression.unwrap_or_default());
+    Ok(forced)
+}
+
+/// Builds an image with the full mip chain where only the levels from `first_level` down contain
+/// data. The larger levels are zeroed and excluded from sampling with the view's `base_mip_level`.
+/// The sampler isn't touched, an `ImageSampler::Default` would otherwise have to be replaced with a
+/// descriptor that doesn't necessarily match the app's default sampler.
+fn partial_mip_image(
+    image: &Image,
+    format: TextureFormat,
+    mip_count: u32,
+    first_level: u32,
+    tail: &[u8],
+) -> Image {
+    let mut descriptor = image.texture_descriptor.clone();
+    descriptor.format = format;
+    descriptor.mip_level_count = mip_count;
+    if format.is_compressed() {
+        descriptor.view_formats = &[];
+    }
+    let missing_size = mip_chain_byte_size(
+        descriptor.size.width,
+        descriptor.size.height,
+        format,
+        first_level,
+    );
+    let mut data = vec![0; missing_size];
+    data.extend_from_slice(tail);
+
+    let mut view_descriptor = image.texture_view_descriptor.clone().unwrap_or_default();
+    view_descriptor.base_mip_level = first_level;
+    view_descriptor.mip_level_count = None;
+
+    Image {
+        data,
+        texture_descriptor: descriptor,
+        sampler: image.sampler.clone(),
+        texture_view_descriptor: Some(view_descriptor),
+        asset_usage: image.asset_usage,
+    }
+}
+
+/// Returns a vec of bytes containing the image data for all generated mips.
+/// Use `calculate_mip_count()` to find the value for `mip_count`.
+///
+/// `mip_count` includes mip 0, so at most `mip_count - 1` levels are generated after it. Level `n`
+/// is `size >> n` like on the GPU (halving and rounding down), and generation stops early once
+/// either side of the next level would drop below 1 pixel, or below 4 pixels if compression is
+/// enabled. So a `mip_count` larger than the natural chain is clamped to it and zero sized levels
+/// are never produced. Images with a height of 1 (the GPU usable form of a 1D texture) only shrink
+/// in width. Unlike `calculate_mip_count`, `minimum_mip_resolution` isn't applied here.
+/// `dyn_image` is left as the smallest generated level.
+/// `color_space` is the color space of the data in `dyn_image`, which `DynamicImage` doesn't track.
+pub fn generate_mips(
+    dyn_image: &mut DynamicImage,
+    color_space: ColorSpace,
+    has_alpha: bool,
+    mip_count: u32,
+    settings: &MipmapGeneratorSettings,
+) -> Vec<u8> {
+    warn_compression_unavailable(settings);
+
+    let mut levels = downsample_levels(dyn_image, color_space, mip_count, settings);
+    let mut image_data = encode_mip_level(dyn_image, has_alpha, settings);
+    for level in &levels {
+        image_data.append(&mut encode_mip_level(level, has_alpha, settings));
+    }
+    if let Some(last) = levels.pop() {
+        *dyn_image = last;
+    }
+    image_data
+}
+
+/// Same as `generate_mips`, but the levels are encoded starting from the smallest one.
+/// After each level except mip 0, `on_levels` is called with that level's index and the data of
+/// that level and all smaller ones, so partial results can be shown before the whole chain is done.
+pub fn generate_mips_progressive(
+    dyn_image: &mut DynamicImage,
+    color_space: ColorSpace,
+    has_alpha: bool,
+    mip_count: u32,
+    settings: &MipmapGeneratorSettings,
+    mut on_levels: impl FnMut(u32, &[u8]),
+) -> Vec<u8> {
+    warn_compression_unavailable(settings);
+
+    let mut levels = downsample_levels(dyn_image, color_space, mip_count, settings);
+
+    // Encoded levels, smallest first
+    let mut encoded_levels = Vec::with_capacity(levels.len() + 1);
+    for (i, level) in levels.iter().enumerate().rev() {
+        encoded_levels.push(encode_mip_level(level, has_alpha, settings));
+        let tail = encoded_levels
+            .iter()
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<u8>>();
+        on_levels(i as u32 + 1, &tail);
+    }
+    encoded_levels.push(encode_mip_level(dyn_image, has_alpha, settings));
+
+    if let Some(last) = levels.pop() {
+        *dyn_image = last;
+    }
+
+    encoded_levels.into_iter().rev().flatten().collect()
+}
+
+#[allow(unused_variables)]
+fn warn_compression_unavailable(settings: &MipmapGeneratorSettings) {
+    #[cfg(not(feature = "compress"))]
+    if settings.compression.is_some() {
+        warn!("Compression is Some but compress feature is disabled. Falling back to generating mips without compression.")
+    }
+}
 
-    let tasks = if let Some(ref mut tasks) = tasks_res {
-        tasks
-    } else {
-        &mut new_tasks
-    };
+/// Downsamples `dyn_image` into the levels below it, mip 0 is not included.
+/// See `generate_mips` for how many levels are returned.
+fn downsample_levels(
+    dyn_image: &DynamicImage,
+    color_space: ColorSpace,
+    mip_count: u32,
+    settings: &MipmapGeneratorSettings,
+) -> Vec<DynamicImage> {
+    let base_width = dyn_image.width();
+    let base_height = dyn_image.height();
+
+    #[cfg(feature = "compress")]
+    let min = if settings.compression.is_some() { 4 } else { 1 };
+    #[cfg(not(feature = "compress"))]
+    let min = 1;
+
+    // A strip with a height of 1 is how a 1D texture gets mips, since wgpu doesn't allow mips
+    // on `TextureDimension::D1`. Its levels keep a height of 1, like on the GPU.
+    let strip = base_height == 1;
+
+    let base_coverage = settings
+        .alpha_coverage_cutoff
+        .and_then(|cutoff| Some((cutoff, alpha_coverage(dyn_image.as_rgba8()?, cutoff, 1.0))));
 
-    let thread_pool = AsyncComputeTaskPool::get();
-    'outer: for event in material_events.read() {
-        let material_h = match event {
-            AssetEvent::Added { id } => id,
-            AssetEvent::LoadedWithDependencies { id } => id,
-            _ => continue,
+    // Previous level at 16 bits per channel, if high_precision is used
+    let mut high_precision_level = settings
+        .high_precision
+        .then(|| to_high_precision(dyn_image))
+        .flatten()
+        .filter(|_| settings.custom_filter.is_none() && !strip);
+
+    let mut levels: Vec<DynamicImage> = Vec::new();
+    // mip_count includes mip 0
+    for level_index in 1..mip_count {
+        // Sized from the base like wgpu (`size >> level`), so the data always matches the size
+        // of the level on the GPU. Levels never reach the `max(1, ..)` that wgpu also applies,
+        // since the chain ends once either side would drop below `min`.
+        let width = base_width >> level_index;
+        let height = if strip { 1 } else { base_height >> level_index };
+        if width < min || height < min {
+            break;
+        }
+        let mut level = if strip {
+            downsample_strip(levels.last().unwrap_or(dyn_image), color_space, settings)
+        } else if let Some(previous) = high_precision_level.take() {
+            let next = downsample(&previous, color_space, width, height, settings);
+            let level = from_high_precision(&next);
+            high_precision_level = Some(next);
+            level
+        } else {
+            downsample(
+                levels.last().unwrap_or(dyn_image),
+                color_space,
+                width,
+                height,
+                settings,
+            )
         };
-        for m in no_mipmap.iter() {
-            if m.id() == *material_h {
-                continue 'outer;
-            }
+        if let Some(strength) = settings.post_downsample_sharpen {
+            sharpen_dyn_image(&mut level, strength);
         }
-        // get_mut(material_h) here so we see the filtering right away
-        // and even if mipmaps aren't made, we still get the filtering
-        if let Some(material) = materials.get_mut(*material_h) {
-            for image_h in material.get_images().into_iter() {
-                if let Some((_, material_handles)) = tasks.get_mut(image_h) {
-                    material_handles.push(Handle::Weak(*material_h));
-                    continue; //There is already a task for this image
-                }
-                if let Some(image) = images.get_mut(image_h) {
-                    let mut descriptor = match image.sampler.clone() {
-                        ImageSampler::Default => default_sampler.0.clone(),
-                        ImageSampler::Descriptor(descriptor) => descriptor,
-                    };
-                    descriptor.anisotropy_clamp = settings.anisotropic_filtering;
-                    image.sampler = ImageSampler::Descriptor(descriptor);
-                    if image.texture_descriptor.mip_level_count == 1
-                        && check_image_compatible(image).is_ok()
-                    {
-                        let mut image = image.clone();
-                        let settings = settings.clone();
-                        let mut added_cache_size = 0;
-                        let task = thread_pool.spawn(async move {
-                            match generate_mips_texture(
-                                &mut image,
-                                &settings.clone(),
-                                &mut added_cache_size,
-                            ) {
-                                Ok(_) => (),
-                                Err(e) => warn!("{}", e),
-                            }
-                            TaskData {
-                                added_cache_size,
-                                image,
-                            }
-                        });
-                        tasks.insert(image_h.clone(), (task, vec![Handle::Weak(*material_h)]));
-                        progress.total += 1;
-                    }
-                }
-            }
+        if let Some((cutoff, coverage)) = base_coverage {
+            preserve_alpha_coverage(&mut level, cutoff, coverage);
+        }
+        if let Some(level_hook) = &settings.level_hook {
+            level_hook(level_index, &mut level);
         }
+        debug_assert_eq!((level.width(), level.height()), (width, height));
+        levels.push(level);
     }
+    levels
+}
 
-    fn bytes_to_gb(bytes: usize) -> usize {
-        bytes / 1024_usize.pow(3)
+/// Returns the 16-bit version of an 8-bit Luma, LumaA or Rgba image.
+fn to_high_precision(dyn_image: &DynamicImage) -> Option<DynamicImage> {
+    match dyn_image {
+        DynamicImage::ImageLuma8(_) => Some(DynamicImage::ImageLuma16(dyn_image.to_luma16())),
+        DynamicImage::ImageLumaA8(_) => {
+            Some(DynamicImage::ImageLumaA16(dyn_image.to_luma_alpha16()))
+        }
+        DynamicImage::ImageRgba8(_) => Some(DynamicImage::ImageRgba16(dyn_image.to_rgba16())),
+        _ => None,
     }
+}
 
-    tasks.retain(|image_h, (task, material_handles)| {
-        match future::block_on(future::poll_once(task)) {
-            Some(task_data) => {
-                if let Some(image) = images.get_mut(image_h) {
-                    *image = task_data.image;
-                    progress.processed += 1;
-                    let prev_cached_data_gb = bytes_to_gb(progress.cached_data_size_bytes);
-                    progress.cached_data_size_bytes += task_data.added_cache_size;
-                    let current_cached_data_gb = bytes_to_gb(progress.cached_data_size_bytes);
-                    if current_cached_data_gb > prev_cached_data_gb {
-                        warn!(
-                            "Generated cached texture data from just this run is {}",
-                            format_bytes_size(progress.cached_data_size_bytes)
-                        );
-                    }
-                    // Touch material to trigger change detection
-                    for material_h in material_handles.iter() {
-                        let _ = materials.get_mut(material_h);
-                    }
-                }
-                false
-            }
-            None => true,
-        }
-    });
+/// Converts an image from `to_high_precision` back to its 8-bit variant.
+fn from_high_precision(dyn_image: &DynamicImage) -> DynamicImage {
+    match dyn_image {
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma8(dyn_image.to_luma8()),
+        DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA8(dyn_image.to_luma_alpha8()),
+        _ => DynamicImage::ImageRgba8(dyn_image.to_rgba8()),
+    }
+}
 
-    if tasks_res.is_none() {
-        commands.insert_resource(new_tasks);
+fn downsample(
+    dyn_image: &DynamicImage,
+    color_space: ColorSpace,
+    width: u32,
+    height: u32,
+    settings: &MipmapGeneratorSettings,
+) -> DynamicImage {
+    match settings.custom_filter {
+        Some(CustomFilter::Bilateral { range_sigma }) => {
+            bilateral_downsample(dyn_image, width, height, range_sigma)
+        }
+        Some(CustomFilter::PerChannel(filters)) => {
+            per_channel_downsample(dyn_image, width, height, filters)
+        }
+        Some(CustomFilter::Kaiser { alpha }) => {
+            let gamma = color_gamma(dyn_image, color_space, settings);
+            kaiser_downsample(dyn_image, width, height, alpha, gamma)
+        }
+        None if settings.renormalize_normals
+            && matches!(
+                dyn_image,
+                DynamicImage::ImageLumaA8(_) | DynamicImage::ImageLumaA16(_)
+            ) =>
+        {
+            downsample_rg_normals(dyn_image, width, height, settings.filter_type)
+        }
+        None if settings.renormalize_normals
+            && matches!(
+                dyn_image,
+                DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgba16(_)
+            ) =>
+        {
+            downsample_rgb_normals(dyn_image, width, height, settings.filter_type)
+        }
+        None if settings.downsample_gamma != GammaMode::Linear
+            && matches!(
+                dyn_image,
+                DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgba16(_)
+            ) =>
+        {
+            downsample_in_linear(
+                dyn_image,
+                width,
+                height,
+                settings.filter_type,
+                settings.downsample_gamma,
+            )
+        }
+        None if settings.linear_color_downsample
+            && color_space == ColorSpace::Srgb
+            && matches!(
+                dyn_image,
+                DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgba16(_)
+            ) =>
+        {
+            downsample_in_linear(
+                dyn_image,
+                width,
+                height,
+                settings.filter_type,
+                GammaMode::Srgb,
+            )
+        }
+        None => dyn_image.resize_exact(width, height, settings.filter_type),
     }
 }
 
-/// `added_cache_size` is for tracking the amount of data that was cached by this call.
-/// Compressed BCn data is cached on disk if cache_compressed_image_data is enabled.
-pub fn generate_mips_texture(
-    image: &mut Image,
+/// Halves the width of an image with a height of 1 by averaging pairs of pixels, the last pixel
+/// of an odd width goes into the last pair. Color is averaged in linear like `downsample` does
+/// for Rgba images. Returns the same variant as the input.
+fn downsample_strip(
+    dyn_image: &DynamicImage,
+    color_space: ColorSpace,
     settings: &MipmapGeneratorSettings,
-    #[allow(unused)] added_cache_size: &mut usize,
-) -> anyhow::Result<()> {
-    check_image_compatible(image)?;
-    match try_into_dynamic(image.clone()) {
-        Ok(mut dyn_image) => {
-            #[allow(unused_mut)]
-            let mut has_alpha = false;
-            #[cfg(feature = "compress")]
-            if let Some(img) = dyn_image.as_rgba8() {
-                for px in img.pixels() {
-                    if px.0[3] != 255 {
-                        has_alpha = true;
-                        break;
-                    }
-                }
+) -> DynamicImage {
+    let gamma = color_gamma(dyn_image, color_space, settings);
+    let source = dyn_image.to_rgba32f();
+    let width = (source.width() / 2).max(1);
+    let strip = ImageBuffer::from_fn(width, 1, |x, _| {
+        let start = 2 * x;
+        let end = if x + 1 == width {
+            source.width()
+        } else {
+            start + 2
+        };
+        let mut sum = [0.0; 4];
+        for i in start..end {
+            let px = source.get_pixel(i, 0).0;
+            for c in 0..4 {
+                sum[c] += if c < 3 { gamma.decode(px[c]) } else { px[c] };
             }
+        }
+        let n = (end - start) as f32;
+        Rgba(std::array::from_fn(|c| {
+            if c < 3 {
+                gamma.encode(sum[c] / n)
+            } else {
+                sum[c] / n
+            }
+        }))
+    });
+    same_variant_as(dyn_image, DynamicImage::ImageRgba32F(strip))
+}
 
-            #[cfg(feature = "compress")]
-            let mut compressed_format = None;
-            #[allow(unused_mut)]
-            let mut compression_speed = settings.compression;
-            #[cfg(feature = "compress")]
-            {
-                if let Some(encoder_setting) = settings.compression {
-                    compressed_format = bcn_equivalent_format_of_dyn_image(
-                        &dyn_image,
-                        image.texture_descriptor.format.is_srgb(),
-                        settings.low_quality,
-                        has_alpha,
-                    )
-                    .ok();
-                    compression_speed = compressed_format.map(|_| encoder_setting);
-                }
+/// Gamma the color of `dyn_image` is filtered in by the downsamples that work in float, the same
+/// choice `downsample` makes for Rgba images. Other variants are filtered as they are.
+fn color_gamma(
+    dyn_image: &DynamicImage,
+    color_space: ColorSpace,
+    settings: &MipmapGeneratorSettings,
+) -> GammaMode {
+    match dyn_image {
+        DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgba16(_) => {
+            if settings.downsample_gamma != GammaMode::Linear {
+                settings.downsample_gamma
+            } else if settings.linear_color_downsample && color_space == ColorSpace::Srgb {
+                GammaMode::Srgb
+            } else {
+                GammaMode::Linear
             }
+        }
+        _ => GammaMode::Linear,
+    }
+}
 
-            #[cfg(feature = "compress")]
-            let mut input_hash = u64::MAX;
-            #[allow(unused_mut)]
-            let mut loaded_from_cache = false;
-            let mut new_image_data = Vec::new();
+/// Converts `image` to the variant of `source`.
+fn same_variant_as(source: &DynamicImage, image: DynamicImage) -> DynamicImage {
+    match source {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(image.to_luma8()),
+        DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma16(image.to_luma16()),
+        DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    }
+}
 
-            #[cfg(feature = "compress")]
-            if compression_speed.is_some() && compressed_format.is_some() {
-                if let Some(cache_path) = &settings.compressed_image_data_cache_path {
-                    input_hash = calculate_hash(&image, &settings);
-                    if let Some(compressed_image_data) = load_from_cache(input_hash, &cache_path) {
-                        new_image_data = compressed_image_data;
-                        loaded_from_cache = true;
-                    }
-                }
-            }
+/// Separable Kaiser windowed sinc resize, see `CustomFilter::Kaiser`. Color is decoded with
+/// `gamma` before filtering and encoded after. Returns the same variant as the input.
+fn kaiser_downsample(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    alpha: f32,
+    gamma: GammaMode,
+) -> DynamicImage {
+    let source = dyn_image.to_rgba32f();
+    let (src_width, src_height) = (source.width() as usize, source.height() as usize);
+    let (width, height) = (width as usize, height as usize);
+    let pixels = source
+        .pixels()
+        .map(|px| {
+            let [r, g, b, a] = px.0;
+            [gamma.decode(r), gamma.decode(g), gamma.decode(b), a]
+        })
+        .collect::<Vec<_>>();
 
-            let mip_count = calculate_mip_count(
-                dyn_image.width(),
-                dyn_image.height(),
-                settings.minimum_mip_resolution,
-                u32::MAX,
-                compression_speed,
+    // Horizontal pass into a width x src_height image, then vertical into width x height
+    let columns = kaiser_weights(src_width, width, alpha);
+    let mut horizontal = vec![[0.0f32; 4]; width * src_height];
+    for y in 0..src_height {
+        let row = &pixels[y * src_width..(y + 1) * src_width];
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            horizontal[y * width + x] = weighted_sum(weights, |i| row[start + i]);
+        }
+    }
+    // Ringing can overshoot the range of integer formats, float images keep their HDR values
+    let is_float = matches!(
+        dyn_image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    let clamp = |c: f32| if is_float { c } else { c.clamp(0.0, 1.0) };
+    let rows = kaiser_weights(src_height, height, alpha);
+    let mut resized = ImageBuffer::new(width as u32, height as u32);
+    for (y, (start, weights)) in rows.iter().enumerate() {
+        for x in 0..width {
+            let [r, g, b, a] = weighted_sum(weights, |i| horizontal[(start + i) * width + x]);
+            resized.put_pixel(
+                x as u32,
+                y as u32,
+                Rgba([
+                    gamma.encode(clamp(r)),
+                    gamma.encode(clamp(g)),
+                    gamma.encode(clamp(b)),
+                    clamp(a),
+                ]),
             );
+        }
+    }
+    same_variant_as(dyn_image, DynamicImage::ImageRgba32F(resized))
+}
 
-            if !loaded_from_cache {
-                new_image_data = generate_mips(&mut dyn_image, has_alpha, mip_count, &settings);
-                #[cfg(feature = "compress")]
-                if let Some(cache_path) = &settings.compressed_image_data_cache_path {
-                    if compression_speed.is_some() && compressed_format.is_some() {
-                        *added_cache_size += new_image_data.len();
-                        save_to_cache(input_hash, &new_image_data, &cache_path).unwrap();
+fn weighted_sum(weights: &[f32], pixel: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (i, weight) in weights.iter().enumerate() {
+        for (sum, value) in sum.iter_mut().zip(pixel(i)) {
+            *sum += value * weight;
+        }
+    }
+    sum
+}
+
+/// For each destination pixel, the first source pixel it reads and the normalized weights of the
+/// source pixels from there on.
+fn kaiser_weights(src_len: usize, dst_len: usize, alpha: f32) -> Vec<(usize, Vec<f32>)> {
+    const RADIUS: f32 = 3.0;
+    // Filter in units of destination pixels, so it widens with the downsample factor
+    let scale = (src_len as f32 / dst_len as f32).max(1.0);
+    let support = RADIUS * scale;
+    let i0_alpha = bessel_i0(alpha);
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * src_len as f32 / dst_len as f32;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(src_len);
+            let mut weights = (start..end)
+                .map(|j| {
+                    let x = (j as f32 + 0.5 - center) / scale;
+                    if x.abs() >= RADIUS {
+                        return 0.0;
                     }
-                }
+                    let window = bessel_i0(alpha * (1.0 - (x / RADIUS).powi(2)).sqrt()) / i0_alpha;
+                    sinc(x) * window
+                })
+                .collect::<Vec<_>>();
+            let total: f32 = weights.iter().sum();
+            if total.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|w| *w /= total);
             }
+            (start, weights)
+        })
+        .collect()
+}
 
-            image.texture_descriptor.mip_level_count = mip_count;
-            #[cfg(feature = "compress")]
-            if let Some(format) = compressed_format {
-                image.texture_descriptor.format = format;
-                // Remove view formats for compressed textures.
-                // TODO Is this an issue? A bit difficult to work around since it's &['static]
-                image.texture_descriptor.view_formats = &[];
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Modified Bessel function of the first kind, order 0, from its power series.
+fn bessel_i0(x: f32) -> f32 {
+    let half_x_sq = (x * 0.5).powi(2);
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..50 {
+        term *= half_x_sq / (k * k) as f32;
+        sum += term;
+        if term < sum * 1e-7 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Converts color to linear with `gamma` before filtering and back after. Alpha is already
+/// linear. Returns the same variant as the input.
+fn downsample_in_linear(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter_type: FilterType,
+    gamma: GammaMode,
+) -> DynamicImage {
+    let mut linear = dyn_image.to_rgba32f();
+    for px in linear.pixels_mut() {
+        for c in &mut px.0[..3] {
+            *c = gamma.decode(*c);
+        }
+    }
+    let mut resized = DynamicImage::ImageRgba32F(linear).resize_exact(width, height, filter_type);
+    for px in resized.as_mut_rgba32f().unwrap().pixels_mut() {
+        for c in &mut px.0[..3] {
+            *c = gamma.encode(*c);
+        }
+    }
+    match dyn_image {
+        DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(resized.to_rgba16()),
+        _ => DynamicImage::ImageRgba8(resized.to_rgba8()),
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Downsamples a two channel normal map (stored as LumaA) by reconstructing Z for each texel,
+/// filtering the full vectors and renormalizing them. Returns the same variant as the input.
+fn downsample_rg_normals(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter_type: FilterType,
+) -> DynamicImage {
+    let rg = dyn_image.to_luma_alpha32f();
+    let vectors = ImageBuffer::from_fn(rg.width(), rg.height(), |x, y| {
+        let [r, g] = rg.get_pixel(x, y).0;
+        let xy = Vec2::new(r, g) * 2.0 - 1.0;
+        let z = (1.0 - xy.length_squared()).max(0.0).sqrt();
+        image::Rgb([xy.x, xy.y, z])
+    });
+    let resized = DynamicImage::ImageRgb32F(vectors).resize_exact(width, height, filter_type);
+    let resized = resized.as_rgb32f().unwrap();
+    let normals = ImageBuffer::from_fn(width, height, |x, y| {
+        let normal = Vec3::from(resized.get_pixel(x, y).0).normalize_or(Vec3::Z);
+        image::LumaA([normal.x * 0.5 + 0.5, normal.y * 0.5 + 0.5])
+    });
+    match dyn_image {
+        DynamicImage::ImageLumaA16(_) => {
+            DynamicImage::ImageLumaA16(DynamicImage::from(normals).to_luma_alpha16())
+        }
+        _ => DynamicImage::ImageLumaA8(DynamicImage::from(normals).to_luma_alpha8()),
+    }
+}
+
+/// Downsamples a normal map that stores the vector in RGB, renormalizing the filtered vectors.
+/// Returns the same variant as the input.
+fn downsample_rgb_normals(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter_type: FilterType,
+) -> DynamicImage {
+    let mut vectors = dyn_image.to_rgba32f();
+    for px in vectors.pixels_mut() {
+        for c in &mut px.0[..3] {
+            *c = *c * 2.0 - 1.0;
+        }
+    }
+    let mut resized = DynamicImage::ImageRgba32F(vectors).resize_exact(width, height, filter_type);
+    for px in resized.as_mut_rgba32f().unwrap().pixels_mut() {
+        let [x, y, z, a] = px.0;
+        let normal = Vec3::new(x, y, z).normalize_or(Vec3::Z) * 0.5 + 0.5;
+        px.0 = [normal.x, normal.y, normal.z, a.clamp(0.0, 1.0)];
+    }
+    same_variant_as(dyn_image, resized)
+}
+
+/// Box downsample where every source pixel in a footprint is weighted by its summed similarity
+/// to the others, see `CustomFilter::Bilateral`. Falls back to a `Triangle` resize for formats
+/// other than Luma8, LumaA8 and Rgba8.
+fn bilateral_downsample(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    range_sigma: f32,
+) -> DynamicImage {
+    let channels = match dyn_image {
+        DynamicImage::ImageLuma8(_) => 1,
+        DynamicImage::ImageLumaA8(_) => 2,
+        DynamicImage::ImageRgba8(_) => 4,
+        _ => return dyn_image.resize_exact(width, height, FilterType::Triangle),
+    };
+    let src_width = dyn_image.width() as usize;
+    let src_height = dyn_image.height() as usize;
+    let src = dyn_image.as_bytes();
+    let (width, height) = (width as usize, height as usize);
+    let inv_two_sigma_sq = 1.0 / (2.0 * (range_sigma * 255.0).powi(2)).max(f32::EPSILON);
+
+    let mut data = vec![0u8; width * height * channels];
+    let mut footprint: Vec<&[u8]> = Vec::with_capacity(9);
+    for y in 0..height {
+        // Source rows and columns covered by this output pixel, at least one of each
+        let y0 = y * src_height / height;
+        let y1 = ((y + 1) * src_height / height).max(y0 + 1);
+        for x in 0..width {
+            let x0 = x * src_width / width;
+            let x1 = ((x + 1) * src_width / width).max(x0 + 1);
+            footprint.clear();
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let i = (sy * src_width + sx) * channels;
+                    footprint.push(&src[i..i + channels]);
+                }
+            }
+            let mut sum = [0.0f32; 4];
+            let mut weight_sum = 0.0;
+            for a in &footprint {
+                let weight: f32 = footprint
+                    .iter()
+                    .map(|b| {
+                        let dist_sq: f32 = a
+                            .iter()
+                            .zip(b.iter())
+                            .map(|(a, b)| (*a as f32 - *b as f32).powi(2))
+                            .sum();
+                        (-dist_sq * inv_two_sigma_sq).exp()
+                    })
+                    .sum();
+                for (sum, value) in sum.iter_mut().zip(a.iter()) {
+                    *sum += *value as f32 * weight;
+                }
+                weight_sum += weight;
+            }
+            let i = (y * width + x) * channels;
+            for (out, sum) in data[i..i + channels].iter_mut().zip(sum) {
+                *out = (sum / weight_sum).round().clamp(0.0, 255.0) as u8;
             }
+        }
+    }
 
-            image.data = new_image_data;
-            Ok(())
+    dyn_image_from_u8(width as u32, height as u32, channels, data)
+}
+
+/// Downsamples with one filter per channel, resizing once per distinct filter and combining the
+/// channels of the results. See `CustomFilter::PerChannel`.
+fn per_channel_downsample(
+    dyn_image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filters: [FilterType; 4],
+) -> DynamicImage {
+    let channels = match dyn_image {
+        DynamicImage::ImageLuma8(_) => 1,
+        DynamicImage::ImageLumaA8(_) => 2,
+        DynamicImage::ImageRgba8(_) => 4,
+        _ => return dyn_image.resize_exact(width, height, filters[0]),
+    };
+    let mut data = dyn_image
+        .resize_exact(width, height, filters[0])
+        .into_bytes();
+    // Channels using the first filter are already done
+    for (i, filter) in filters.iter().enumerate().take(channels).skip(1) {
+        if filters[..i].contains(filter) {
+            continue;
+        }
+        let resized = dyn_image.resize_exact(width, height, *filter);
+        for (j, (out, value)) in data.iter_mut().zip(resized.as_bytes()).enumerate() {
+            if filters[j % channels] == *filter {
+                *out = *value;
+            }
         }
-        Err(e) => Err(e),
     }
+    dyn_image_from_u8(width, height, channels, data)
 }
 
-/// Returns a vec of bytes containing the image data for all generated mips.
-/// Use `calculate_mip_count()` to find the value for `mip_count`.
-pub fn generate_mips(
-    dyn_image: &mut DynamicImage,
+/// Builds an 8 bit R, RG or RGBA image from tightly packed data.
+fn dyn_image_from_u8(width: u32, height: u32, channels: usize, data: Vec<u8>) -> DynamicImage {
+    match channels {
+        1 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, data).unwrap()),
+        2 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, data).unwrap()),
+        _ => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).unwrap()),
+    }
+}
+
+/// Returns the data of a single mip level, compressed if compression is enabled.
+fn encode_mip_level(
+    dyn_image: &DynamicImage,
     #[allow(unused_variables)] has_alpha: bool,
-    mip_count: u32,
-    settings: &MipmapGeneratorSettings,
+    #[allow(unused_variables)] settings: &MipmapGeneratorSettings,
 ) -> Vec<u8> {
-    let mut width = dyn_image.width();
-    let mut height = dyn_image.height();
-
     #[allow(unused_mut)]
     let mut compressed_image_data = None;
     #[cfg(feature = "compress")]
-    if let Some(compression_settings) = settings.compression {
+    if let Some(compression_speed) = settings.compression {
         compressed_image_data = bcn_compress_dyn_image(
-            compression_settings,
+            compression_speed,
             dyn_image,
             has_alpha,
             settings.low_quality,
+            settings.use_bc3_for_rgba,
         )
         .ok();
     }
-
-    #[cfg(not(feature = "compress"))]
-    if settings.compression.is_some() {
-        warn!("Compression is Some but compress feature is disabled. Falling back to generating mips without compression.")
-    }
-
-    let mut image_data = compressed_image_data.unwrap_or(dyn_image.as_bytes().to_vec());
-
-    #[cfg(feature = "compress")]
-    let min = if settings.compression.is_some() { 4 } else { 1 };
-    #[cfg(not(feature = "compress"))]
-    let min = 1;
-
-    for _ in 0..mip_count {
-        width /= 2;
-        height /= 2;
-        *dyn_image = dyn_image.resize_exact(width, height, settings.filter_type);
-
-        #[allow(unused_mut)]
-        let mut compressed_image_data = None;
-        #[cfg(feature = "compress")]
-        if let Some(compression_speed) = settings.compression {
-            compressed_image_data = bcn_compress_dyn_image(
-                compression_speed,
-                dyn_image,
-                has_alpha,
-                settings.low_quality,
-            )
-            .ok();
-        }
-        image_data.append(&mut compressed_image_data.unwrap_or(dyn_image.as_bytes().to_vec()));
-        if width <= min || height <= min {
+    compressed_image_data.unwrap_or_else(|| dyn_image.as_bytes().to_vec())
+}
+
+/// Fraction of pixels with an alpha above `cutoff` after scaling alpha by `scale`.
+fn alpha_coverage(image: &ImageBuffer<Rgba<u8>, Vec<u8>>, cutoff: f32, scale: f32) -> f32 {
+    let cutoff = cutoff * 255.0;
+    let covered = image
+        .pixels()
+        .filter(|px| (px.0[3] as f32 * scale).min(255.0) > cutoff)
+        .count();
+    covered as f32 / (image.width() * image.height()).max(1) as f32
+}
+
+/// Scales the alpha channel so the fraction of pixels above `cutoff` matches `target_coverage`.
+/// Coverage only grows with the scale, so a binary search finds the scale that matches best.
+/// Only RGBA images are affected.
+fn preserve_alpha_coverage(dyn_image: &mut DynamicImage, cutoff: f32, target_coverage: f32) {
+    let DynamicImage::ImageRgba8(image) = dyn_image else {
+        return;
+    };
+    let mut min_scale = 0.0;
+    let mut max_scale = 4.0;
+    let mut scale = 1.0;
+    for _ in 0..10 {
+        let coverage = alpha_coverage(image, cutoff, scale);
+        if (coverage - target_coverage).abs() < 0.001 {
             break;
+        } else if coverage < target_coverage {
+            min_scale = scale;
+        } else {
+            max_scale = scale;
         }
+        scale = (min_scale + max_scale) * 0.5;
     }
+    for px in image.pixels_mut() {
+        px.0[3] = (px.0[3] as f32 * scale).round().min(255.0) as u8;
+    }
+}
 
-    image_data
+/// Unsharp mask, adds `strength` times the difference from a blurred copy back onto the image.
+/// The alpha channel of RGBA images is left untouched.
+fn sharpen_dyn_image(dyn_image: &mut DynamicImage, strength: f32) {
+    let blurred = dyn_image.blur(0.75);
+    let (bytes, channels, skip_alpha): (&mut [u8], usize, bool) = match dyn_image {
+        DynamicImage::ImageLuma8(data) => (data, 1, false),
+        DynamicImage::ImageLumaA8(data) => (data, 2, false),
+        DynamicImage::ImageRgba8(data) => (data, 4, true),
+        _ => return,
+    };
+    for (i, (value, blurred)) in bytes.iter_mut().zip(blurred.as_bytes()).enumerate() {
+        if skip_alpha && i % channels == channels - 1 {
+            continue;
+        }
+        let v = *value as f32;
+        *value = (v + (v - *blurred as f32) * strength)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+    }
 }
 
 /// Returns the number of mip levels
@@ -516,94 +4188,349 @@ pub fn calculate_mip_count(
     #[cfg(not(feature = "compress"))]
     let min = 1;
 
+    // Strips with a height of 1 only shrink along their width, see `downsample_levels`
+    let strip = height == 1 && min == 1;
+
     // Use log to avoid loop? Are there edge cases with rounding?
 
     while width / 2 >= minimum_mip_resolution.max(min)
-        && height / 2 >= minimum_mip_resolution.max(min)
+        && (strip || height / 2 >= minimum_mip_resolution.max(min))
         && mip_level_count < max_mip_count
     {
         width /= 2;
-        height /= 2;
+        height = (height / 2).max(1);
         mip_level_count += 1;
     }
 
     mip_level_count
 }
 
-/// Extract a specific individual mip level as a new image.
-pub fn extract_mip_level(image: &Image, mip_level: u32) -> anyhow::Result<Image> {
-    check_image_compatible(image)?;
+/// Number of mip levels (including mip 0) the sampler can access, based on its `lod_max_clamp`.
+/// Used as the `max_mip_count` for generation, so a sampler from a loader that limits the lod
+/// (e.g. KTX2) doesn't get levels it will never sample. `minimum_mip_resolution` still applies,
+/// whichever of the two allows fewer levels wins. `ImageSampler::Default` is unlimited.
+pub fn sampler_max_mip_count(sampler: &ImageSampler) -> u32 {
+    match sampler {
+        ImageSampler::Descriptor(descriptor) if descriptor.lod_max_clamp < 32.0 => {
+            descriptor.lod_max_clamp.max(0.0).floor() as u32 + 1
+        }
+        _ => u32::MAX,
+    }
+}
 
-    let descriptor = &image.texture_descriptor;
+/// Upper limit for the number of levels (including mip 0) generated for `image`.
+fn max_mip_count(image: &Image, settings: &MipmapGeneratorSettings) -> u32 {
+    if settings.compress_only {
+        1
+    } else {
+        sampler_max_mip_count(&image.sampler)
+    }
+}
 
-    if descriptor.mip_level_count < mip_level {
-        return Err(anyhow!(
-            "Mip level {mip_level} requested, but only {} are avaliable.",
-            descriptor.mip_level_count
-        ));
+/// Estimated GPU memory use of a set of images, see `estimate_memory`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Total size of the images as they are currently.
+    pub before_bytes: usize,
+    /// Total size after mipmap generation (and compression, if enabled) would be applied.
+    pub after_bytes: usize,
+    /// Number of images that would be processed.
+    pub processed_images: usize,
+}
+
+/// Estimates the total GPU memory of all images before and after processing with `settings`,
+/// without processing anything. Images that already have mips or aren't compatible are counted
+/// as unchanged. For RGBA images in low quality mode BC3 is assumed, since detecting whether
+/// BC1 can be used would require scanning the alpha channel.
+pub fn estimate_memory(
+    images: &Assets<Image>,
+    settings: &MipmapGeneratorSettings,
+) -> MemoryEstimate {
+    let mut estimate = MemoryEstimate::default();
+    for (_, image) in images.iter() {
+        let descriptor = &image.texture_descriptor;
+        let layers = descriptor.size.depth_or_array_layers as usize;
+        let before = layers
+            * mip_chain_byte_size(
+                descriptor.size.width,
+                descriptor.size.height,
+                descriptor.format,
+                descriptor.mip_level_count,
+            );
+        estimate.before_bytes += before;
+        if descriptor.mip_level_count != 1
+            || check_image_compatible(image, settings).is_err()
+            || !is_dynamic_compatible_format(descriptor.format)
+        {
+            estimate.after_bytes += before;
+            continue;
+        }
+        let (width, height) =
+            scaled_base_size(descriptor.size.width, descriptor.size.height, settings);
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let compression = settings
+            .compression
+            .filter(|_| cfg!(feature = "compress"))
+            .and_then(|_| estimated_compressed_format(descriptor.format, size, settings));
+        let fallback = settings
+            .compression
+            .filter(|_| cfg!(feature = "compress") && compression.is_none())
+            .and_then(|_| settings.compression_fallback.format_for(descriptor.format));
+        let mip_count = calculate_mip_count(
+            width,
+            height,
+            settings.minimum_mip_resolution,
+            max_mip_count(image, settings),
+            compression.and(settings.compression),
+        );
+        estimate.after_bytes += layers
+            * mip_chain_byte_size(
+                width,
+                height,
+                compression.or(fallback).unwrap_or(descriptor.format),
+                mip_count,
+            );
+        estimate.processed_images += 1;
+    }
+    estimate
+}
+
+/// Mirrors `bcn_equivalent_format_of_dyn_image` from the texture format alone.
+fn estimated_compressed_format(
+    format: TextureFormat,
+    size: Extent3d,
+    settings: &MipmapGeneratorSettings,
+) -> Option<TextureFormat> {
+    if size.width < 4 || size.height < 4 {
+        return None;
     }
+    if let Some(forced) = settings.force_output_format {
+        return forced.is_compressed().then_some(forced);
+    }
+    let is_rgba8 = matches!(
+        format.remove_srgb_suffix(),
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm
+    );
+    let chosen = settings
+        .compression_format
+        .format(ColorSpace::of_format(format))
+        .filter(|_| is_rgba8);
+    let compressed = if let Some(chosen) = chosen {
+        chosen
+    } else if settings.low_quality {
+        match format {
+            TextureFormat::R8Unorm => TextureFormat::Bc4RUnorm,
+            TextureFormat::Rg8Unorm => TextureFormat::Bc1RgbaUnorm,
+            TextureFormat::Rgba8Unorm => TextureFormat::Bc3RgbaUnorm,
+            TextureFormat::Rgba8UnormSrgb => TextureFormat::Bc3RgbaUnormSrgb,
+            TextureFormat::Bgra8Unorm => TextureFormat::Bc3RgbaUnorm,
+            TextureFormat::Bgra8UnormSrgb => TextureFormat::Bc3RgbaUnormSrgb,
+            TextureFormat::Rgba16Float | TextureFormat::Rgba32Float => TextureFormat::Bc6hRgbUfloat,
+            _ => return None,
+        }
+    } else {
+        let compressed = compressible_formats()
+            .iter()
+            .find(|(source, _)| *source == format)
+            .map(|(_, compressed)| *compressed)?;
+        if settings.use_bc3_for_rgba {
+            bc7_to_bc3(compressed)
+        } else {
+            compressed
+        }
+    };
+    Some(
+        settings
+            .compressed_color_space
+            .and_then(|color_space| bcn_format_with_color_space(compressed, color_space).ok())
+            .unwrap_or(compressed),
+    )
+}
+
+/// Byte size of a 2D texture with `mip_count` levels, in the tightly packed layout of
+/// `Image::data`. Each level is half the size of the previous one (at least 1 pixel) and is
+/// rounded up to whole blocks for compressed formats, so a 2x2 BC7 level still takes 16 bytes.
+/// Returns 0 for formats without a block size, like combined depth/stencil formats.
+pub fn mip_chain_byte_size(
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    mip_count: u32,
+) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let Some(block_size) = format.block_copy_size(None) else {
+        return 0;
+    };
+    (0..mip_count)
+        .map(|level| {
+            let width = (width >> level).max(1).div_ceil(block_width);
+            let height = (height >> level).max(1).div_ceil(block_height);
+            width as usize * height as usize * block_size as usize
+        })
+        .sum()
+}
 
-    let block_size = descriptor.format.block_copy_size(None).unwrap() as usize;
+/// Extract a specific individual mip level as a new image.
+/// `mip_level` is 1 based: 1 is the full size level and `mip_level_count` the smallest one. Use
+/// `extract_mip_range` for 0 based levels.
+/// Each level is tightly packed after the previous one, with every level half the size of the
+/// previous one. Compressed (BCn) levels are rounded up to whole blocks, see
+/// `mip_chain_byte_size`. Errors instead of panicking if the image data is shorter than the
+/// requested level needs.
+pub fn extract_mip_level(image: &Image, mip_level: u32) -> Result<Image, MipmapError> {
+    let descriptor = &image.texture_descriptor;
 
-    //let mip_factor = 2u32.pow(mip_level - 1);
-    //let final_width = descriptor.size.width/mip_factor;
-    //let final_height = descriptor.size.height/mip_factor;
+    if descriptor.dimension != TextureDimension::D2 {
+        return Err(MipmapError::UnsupportedDimension(descriptor.dimension));
+    }
+    if descriptor.size.depth_or_array_layers != 1 {
+        return Err(MipmapError::UnsupportedLayerCount(
+            descriptor.size.depth_or_array_layers,
+        ));
+    }
 
-    let mut width = descriptor.size.width as usize;
-    let mut height = descriptor.size.height as usize;
+    if mip_level == 0 || mip_level > descriptor.mip_level_count {
+        return Err(MipmapError::MipLevelOutOfRange {
+            mip_level,
+            available: descriptor.mip_level_count,
+        });
+    }
 
-    let mut byte_offset = 0usize;
+    // Combined depth/stencil formats only have a size per aspect, and neither aspect on its own
+    // matches how the data is laid out here
+    let format = descriptor.format;
+    if format.block_copy_size(None).is_none() {
+        return Err(MipmapError::NoBlockCopySize(format));
+    }
 
-    for _ in 0..mip_level - 1 {
-        byte_offset += width * block_size * height;
-        width /= 2;
-        height /= 2;
+    let width = descriptor.size.width;
+    let height = descriptor.size.height;
+    let byte_offset = mip_chain_byte_size(width, height, format, mip_level - 1);
+    let byte_end = mip_chain_byte_size(width, height, format, mip_level);
+    if byte_end > image.data.len() {
+        return Err(MipmapError::DataTooShort {
+            len: image.data.len(),
+            needed: byte_end,
+            mip_level,
+        });
     }
 
     let mut new_descriptor = descriptor.clone();
 
     new_descriptor.mip_level_count = 1;
-    new_descriptor.size = Extent3d {
-        width: width as u32,
-        height: height as u32,
-        depth_or_array_layers: 1,
-    };
+    new_descriptor.size = descriptor
+        .size
+        .mip_level_size(mip_level - 1, descriptor.dimension);
 
     Ok(Image {
-        data: image.data[byte_offset..byte_offset + (width * block_size * height)].to_vec(),
+        data: image.data[byte_offset..byte_end].to_vec(),
         texture_descriptor: new_descriptor,
         sampler: image.sampler.clone(),
         texture_view_descriptor: image.texture_view_descriptor.clone(),
-        asset_usage: RenderAssetUsages::default(),
+        asset_usage: image.asset_usage,
     })
 }
 
-pub fn check_image_compatible(image: &Image) -> anyhow::Result<()> {
-    if image.is_compressed() {
-        return Err(anyhow!("Compressed images not supported"));
-    }
+/// Extract `count` consecutive mip levels starting at `start` as a new image, whose mip 0 is
+/// level `start` of `image`. Unlike `extract_mip_level`, `start` is 0 based (like wgpu's
+/// `base_mip_level`), so `extract_mip_range(image, 2, mip_level_count - 2)` drops the 2 largest
+/// levels. Also works with compressed (BCn) images.
+pub fn extract_mip_range(image: &Image, start: u32, count: u32) -> Result<Image, MipmapError> {
     let descriptor = &image.texture_descriptor;
 
     if descriptor.dimension != TextureDimension::D2 {
-        return Err(anyhow!(
-            "Image has dimension {:?} but only TextureDimension::D2 is supported.",
-            descriptor.dimension
-        ));
+        return Err(MipmapError::UnsupportedDimension(descriptor.dimension));
     }
-
     if descriptor.size.depth_or_array_layers != 1 {
-        return Err(anyhow!(
-            "Image contains {} layers only a single layer is supported.",
-            descriptor.size.depth_or_array_layers
+        return Err(MipmapError::UnsupportedLayerCount(
+            descriptor.size.depth_or_array_layers,
         ));
     }
+    if count == 0 || start.saturating_add(count) > descriptor.mip_level_count {
+        return Err(MipmapError::MipRangeOutOfRange {
+            start,
+            end: start.saturating_add(count),
+            available: descriptor.mip_level_count,
+        });
+    }
+
+    let width = descriptor.size.width;
+    let height = descriptor.size.height;
+    let format = descriptor.format;
+    let byte_offset = mip_chain_byte_size(width, height, format, start);
+    let byte_end = mip_chain_byte_size(width, height, format, start + count);
+    if byte_end > image.data.len() {
+        return Err(MipmapError::DataTooShort {
+            len: image.data.len(),
+            needed: byte_end,
+            mip_level: start + count - 1,
+        });
+    }
+
+    let mut new_descriptor = descriptor.clone();
+    new_descriptor.mip_level_count = count;
+    new_descriptor.size = descriptor.size.mip_level_size(start, descriptor.dimension);
+
+    Ok(Image {
+        data: image.data[byte_offset..byte_end].to_vec(),
+        texture_descriptor: new_descriptor,
+        sampler: image.sampler.clone(),
+        texture_view_descriptor: image.texture_view_descriptor.clone(),
+        asset_usage: image.asset_usage,
+    })
+}
+
+/// Checks that mips can be generated for `image` with `settings`: uncompressed 2D images,
+/// including 2D arrays. Cubemaps are only accepted with `cubemap_seamless`, see `is_cubemap`.
+pub fn check_image_compatible(
+    image: &Image,
+    settings: &MipmapGeneratorSettings,
+) -> Result<(), MipmapError> {
+    if image.is_compressed() {
+        return Err(MipmapError::CompressedInput);
+    }
+    let descriptor = &image.texture_descriptor;
+
+    if descriptor.dimension != TextureDimension::D2 {
+        return Err(MipmapError::UnsupportedDimension(descriptor.dimension));
+    }
+    if is_cubemap(image) && !settings.cubemap_seamless {
+        return Err(MipmapError::CubemapNotEnabled);
+    }
 
     Ok(())
 }
 
-// Implement the GetImages trait for any materials that need conversion
+/// Whether `image` is a cubemap: its texture view is `TextureViewDimension::Cube` or
+/// `CubeArray`, or it has 6 layers and no texture view descriptor. A 6 layer image with a
+/// `D2Array` view is a plain array.
+pub fn is_cubemap(image: &Image) -> bool {
+    match image
+        .texture_view_descriptor
+        .as_ref()
+        .and_then(|view| view.dimension)
+    {
+        Some(dimension) => matches!(
+            dimension,
+            TextureViewDimension::Cube | TextureViewDimension::CubeArray
+        ),
+        None => image.texture_descriptor.size.depth_or_array_layers == 6,
+    }
+}
+
+// Implement the GetImages trait for any materials that need conversion, or derive it to collect
+// every `Handle<Image>` and `Option<Handle<Image>>` field
 pub trait GetImages {
     fn get_images(&self) -> Vec<&Handle<Image>>;
+    /// Optionally report how images are used. Images not listed here fall back to
+    /// `TextureRole::from_format`.
+    fn get_image_roles(&self) -> Vec<(&Handle<Image>, TextureRole)> {
+        Vec::new()
+    }
 }
 
 impl GetImages for StandardMaterial {
@@ -619,25 +4546,64 @@ impl GetImages for StandardMaterial {
         .flatten()
         .collect()
     }
-}
 
-impl<T: GetImages + MaterialExtension> GetImages for ExtendedMaterial<StandardMaterial, T> {
-    fn get_images(&self) -> Vec<&Handle<Image>> {
-        vec![
-            &self.base.base_color_texture,
-            &self.base.emissive_texture,
-            &self.base.metallic_roughness_texture,
-            &self.base.normal_map_texture,
-            &self.base.occlusion_texture,
+    fn get_image_roles(&self) -> Vec<(&Handle<Image>, TextureRole)> {
+        [
+            (&self.base_color_texture, TextureRole::Color),
+            (&self.emissive_texture, TextureRole::Color),
+            (&self.metallic_roughness_texture, TextureRole::Data),
+            (&self.normal_map_texture, TextureRole::Normal),
+            (&self.occlusion_texture, TextureRole::Data),
         ]
         .into_iter()
-        .flatten()
-        .chain(self.extension.get_images())
+        .filter_map(|(image_h, role)| image_h.as_ref().map(|image_h| (image_h, role)))
         .collect()
     }
 }
 
-pub fn try_into_dynamic(image: Image) -> anyhow::Result<DynamicImage> {
+/// Merges the images of the base material and the extension, so an extension only needs to list
+/// its own images.
+impl<B: GetImages + Material, E: GetImages + MaterialExtension> GetImages
+    for ExtendedMaterial<B, E>
+{
+    fn get_images(&self) -> Vec<&Handle<Image>> {
+        self.base
+            .get_images()
+            .into_iter()
+            .chain(self.extension.get_images())
+            .collect()
+    }
+
+    fn get_image_roles(&self) -> Vec<(&Handle<Image>, TextureRole)> {
+        self.base
+            .get_image_roles()
+            .into_iter()
+            .chain(self.extension.get_image_roles())
+            .collect()
+    }
+}
+
+/// Formats that `try_into_dynamic` can convert.
+pub fn is_dynamic_compatible_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::R8Unorm
+            | TextureFormat::Rg8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Rgba8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::R16Unorm
+            | TextureFormat::Rg16Unorm
+            | TextureFormat::Rgba16Float
+            | TextureFormat::Rgba32Float
+    )
+}
+
+/// Rgba16Float and Rgba32Float both become Rgba32F. Bgra8 becomes Rgba8, with red and blue
+/// swapped into place. R16Unorm and Rg16Unorm become Luma16 and LumaA16, they have no BCn
+/// equivalent and stay uncompressed unless `compression_fallback` converts them.
+pub fn try_into_dynamic(image: Image) -> Result<DynamicImage, MipmapError> {
     match image.texture_descriptor.format {
         TextureFormat::R8Unorm => ImageBuffer::from_raw(
             image.texture_descriptor.size.width,
@@ -663,20 +4629,121 @@ pub fn try_into_dynamic(image: Image) -> anyhow::Result<DynamicImage> {
             image.data,
         )
         .map(DynamicImage::ImageRgba8),
+        TextureFormat::Bgra8UnormSrgb | TextureFormat::Bgra8Unorm => ImageBuffer::from_raw(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+            swap_red_blue(image.data),
+        )
+        .map(DynamicImage::ImageRgba8),
+        TextureFormat::R16Unorm => ImageBuffer::from_raw(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+            u16_from_le_bytes(&image.data),
+        )
+        .map(DynamicImage::ImageLuma16),
+        TextureFormat::Rg16Unorm => ImageBuffer::from_raw(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+            u16_from_le_bytes(&image.data),
+        )
+        .map(DynamicImage::ImageLumaA16),
+        TextureFormat::Rgba16Float => ImageBuffer::from_raw(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+            image
+                .data
+                .chunks_exact(2)
+                .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+                .collect(),
+        )
+        .map(DynamicImage::ImageRgba32F),
+        TextureFormat::Rgba32Float => ImageBuffer::from_raw(
+            image.texture_descriptor.size.width,
+            image.texture_descriptor.size.height,
+            image
+                .data
+                .chunks_exact(4)
+                .map(|float| f32::from_le_bytes([float[0], float[1], float[2], float[3]]))
+                .collect(),
+        )
+        .map(DynamicImage::ImageRgba32F),
         // Throw and error if conversion isn't supported
-        texture_format => {
-            return Err(anyhow!(
-                "Conversion into dynamic image not supported for {:?}.",
-                texture_format
-            ))
+        texture_format => return Err(MipmapError::UnsupportedFormat(texture_format)),
+    }
+    .ok_or(MipmapError::InvalidData(image.texture_descriptor.format))
+}
+
+/// Image data is little endian.
+fn u16_from_le_bytes(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|value| u16::from_le_bytes([value[0], value[1]]))
+        .collect()
+}
+
+/// Swaps the first and third byte of every pixel, converting between Rgba8 and Bgra8.
+fn swap_red_blue(mut data: Vec<u8>) -> Vec<u8> {
+    for px in data.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+    data
+}
+
+/// Converts half float bits to f32.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    match exponent {
+        0 => {
+            // Zero and subnormals
+            let value = mantissa as f32 * 2f32.powi(-24);
+            if sign != 0 {
+                -value
+            } else {
+                value
+            }
         }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
     }
-    .ok_or_else(|| {
-        anyhow!(
-            "Failed to convert into {:?}.",
-            image.texture_descriptor.format
-        )
-    })
+}
+
+/// Converts an f32 to the bits of the nearest half float. Values out of range become infinity.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16) & 0x8000;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return (sign | 0x7c00 | nan) as u16;
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return (sign | 0x7c00) as u16;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign as u16;
+        }
+        // Subnormal, shift the mantissa with its implicit leading 1 into place
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return (sign | ((mantissa >> shift) + round)) as u16;
+    }
+    let round = (mantissa >> 12) & 1;
+    // A carry from rounding correctly moves into the exponent
+    ((sign | ((half_exponent as u32) << 10) | (mantissa >> 13)) + round) as u16
+}
+
+/// Converts tightly packed f32 data, like an Rgba32F mip chain, to little endian half floats.
+fn f32_data_to_f16(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|float| {
+            f32_to_f16(f32::from_ne_bytes([float[0], float[1], float[2], float[3]])).to_le_bytes()
+        })
+        .collect()
 }
 
 #[cfg(feature = "compress")]
@@ -685,23 +4752,71 @@ fn bcn_compress_dyn_image(
     dyn_image: &DynamicImage,
     has_alpha: bool,
     low_quality: bool,
+    bc3_for_rgba: bool,
 ) -> anyhow::Result<Vec<u8>> {
-    use image::Rgba;
-
+    // The encoders only compress whole 4x4 blocks, partial blocks at the right and bottom edges
+    // are filled by repeating the last column and row
+    let padded;
+    let (width, height) = (dyn_image.width(), dyn_image.height());
+    let dyn_image = if (width.next_multiple_of(4), height.next_multiple_of(4)) != (width, height) {
+        padded = pad_to_blocks(dyn_image);
+        &padded
+    } else {
+        dyn_image
+    };
     let width = dyn_image.width();
     let height = dyn_image.height();
     let mut image_data;
+    // BC6H is the only HDR format, low_quality has no smaller alternative
+    if let DynamicImage::ImageRgba32F(data) = dyn_image {
+        // The encoder only supports the unsigned variant, so negative values are clamped to 0
+        let non_negative: Vec<u8> = data
+            .as_raw()
+            .iter()
+            .flat_map(|c| c.max(0.0).to_ne_bytes())
+            .collect();
+        let half_data = f32_data_to_f16(&non_negative);
+        image_data = vec![0u8; intel_tex_2::bc6h::calc_output_size(width, height)];
+        let settings = compression_speed.get_bc6h_encoder();
+        compress_block_rows(
+            width,
+            height,
+            8,
+            &half_data,
+            &mut image_data,
+            |height, data, out| {
+                // BC6H reads half floats through the same surface type, with 8 bytes per pixel
+                let surface = intel_tex_2::RgbaSurface {
+                    width,
+                    height,
+                    stride: width * 8,
+                    data,
+                };
+                intel_tex_2::bc6h::compress_blocks_into(&settings, &surface, out);
+            },
+        );
+        return Ok(image_data);
+    }
     if low_quality {
         match dyn_image {
             DynamicImage::ImageLuma8(data) => {
                 image_data = vec![0u8; intel_tex_2::bc4::calc_output_size(width, height)];
-                let surface = intel_tex_2::RSurface {
+                compress_block_rows(
                     width,
                     height,
-                    stride: width,
+                    1,
                     data,
-                };
-                intel_tex_2::bc4::compress_blocks_into(&surface, &mut image_data);
+                    &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RSurface {
+                            width,
+                            height,
+                            stride: width,
+                            data,
+                        };
+                        intel_tex_2::bc4::compress_blocks_into(&surface, out);
+                    },
+                );
             }
             DynamicImage::ImageLumaA8(data) => {
                 let mut rgba =
@@ -711,33 +4826,60 @@ fn bcn_compress_dyn_image(
                     rgba_px.0[1] = rg_px.0[1];
                 }
                 image_data = vec![0u8; intel_tex_2::bc1::calc_output_size(width, height)];
-                let surface = intel_tex_2::RgbaSurface {
+                compress_block_rows(
                     width,
                     height,
-                    stride: width * 4,
-                    data: rgba.as_raw(),
-                };
-                intel_tex_2::bc1::compress_blocks_into(&surface, &mut image_data);
+                    4,
+                    &rgba,
+                    &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RgbaSurface {
+                            width,
+                            height,
+                            stride: width * 4,
+                            data,
+                        };
+                        intel_tex_2::bc1::compress_blocks_into(&surface, out);
+                    },
+                );
             }
             DynamicImage::ImageRgba8(data) => {
                 if has_alpha {
                     image_data = vec![0u8; intel_tex_2::bc3::calc_output_size(width, height)];
-                    let surface = intel_tex_2::RgbaSurface {
+                    compress_block_rows(
                         width,
                         height,
-                        stride: width * 4,
+                        4,
                         data,
-                    };
-                    intel_tex_2::bc3::compress_blocks_into(&surface, &mut image_data);
+                        &mut image_data,
+                        |height, data, out| {
+                            let surface = intel_tex_2::RgbaSurface {
+                                width,
+                                height,
+                                stride: width * 4,
+                                data,
+                            };
+                            intel_tex_2::bc3::compress_blocks_into(&surface, out);
+                        },
+                    );
                 } else {
                     image_data = vec![0u8; intel_tex_2::bc1::calc_output_size(width, height)];
-                    let surface = intel_tex_2::RgbaSurface {
+                    compress_block_rows(
                         width,
                         height,
-                        stride: width * 4,
+                        4,
                         data,
-                    };
-                    intel_tex_2::bc1::compress_blocks_into(&surface, &mut image_data);
+                        &mut image_data,
+                        |height, data, out| {
+                            let surface = intel_tex_2::RgbaSurface {
+                                width,
+                                height,
+                                stride: width * 4,
+                                data,
+                            };
+                            intel_tex_2::bc1::compress_blocks_into(&surface, out);
+                        },
+                    );
                 }
             }
             // Throw and error if conversion isn't supported
@@ -752,36 +4894,79 @@ fn bcn_compress_dyn_image(
         match dyn_image {
             DynamicImage::ImageLuma8(data) => {
                 image_data = vec![0u8; intel_tex_2::bc4::calc_output_size(width, height)];
-                let surface = intel_tex_2::RSurface {
+                compress_block_rows(
                     width,
                     height,
-                    stride: width,
+                    1,
                     data,
-                };
-                intel_tex_2::bc4::compress_blocks_into(&surface, &mut image_data);
+                    &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RSurface {
+                            width,
+                            height,
+                            stride: width,
+                            data,
+                        };
+                        intel_tex_2::bc4::compress_blocks_into(&surface, out);
+                    },
+                );
             }
             DynamicImage::ImageLumaA8(data) => {
                 image_data = vec![0u8; intel_tex_2::bc5::calc_output_size(width, height)];
-                let surface = intel_tex_2::RgSurface {
+                compress_block_rows(
                     width,
                     height,
-                    stride: width * 2,
+                    2,
                     data,
-                };
-                intel_tex_2::bc5::compress_blocks_into(&surface, &mut image_data);
+                    &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RgSurface {
+                            width,
+                            height,
+                            stride: width * 2,
+                            data,
+                        };
+                        intel_tex_2::bc5::compress_blocks_into(&surface, out);
+                    },
+                );
+            }
+            DynamicImage::ImageRgba8(data) if bc3_for_rgba => {
+                image_data = vec![0u8; intel_tex_2::bc3::calc_output_size(width, height)];
+                compress_block_rows(
+                    width,
+                    height,
+                    4,
+                    data,
+                    &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RgbaSurface {
+                            width,
+                            height,
+                            stride: width * 4,
+                            data,
+                        };
+                        intel_tex_2::bc3::compress_blocks_into(&surface, out);
+                    },
+                );
             }
             DynamicImage::ImageRgba8(data) => {
                 image_data = vec![0u8; intel_tex_2::bc7::calc_output_size(width, height)];
-                let surface = intel_tex_2::RgbaSurface {
+                let settings = compression_speed.get_bc7_encoder(has_alpha);
+                compress_block_rows(
                     width,
                     height,
-                    stride: width * 4,
+                    4,
                     data,
-                };
-                intel_tex_2::bc7::compress_blocks_into(
-                    &compression_speed.get_bc7_encoder(has_alpha),
-                    &surface,
                     &mut image_data,
+                    |height, data, out| {
+                        let surface = intel_tex_2::RgbaSurface {
+                            width,
+                            height,
+                            stride: width * 4,
+                            data,
+                        };
+                        intel_tex_2::bc7::compress_blocks_into(&settings, &surface, out);
+                    },
                 );
             }
             // Throw and error if conversion isn't supported
@@ -797,15 +4982,113 @@ fn bcn_compress_dyn_image(
     Ok(image_data)
 }
 
+/// Extends `dyn_image` to a multiple of 4 pixels on each side by repeating its edge pixels. Only
+/// the variants `bcn_compress_dyn_image` supports are padded, others are returned as they are.
+#[cfg(feature = "compress")]
+fn pad_to_blocks(dyn_image: &DynamicImage) -> DynamicImage {
+    fn pad<P: image::Pixel>(
+        image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>> {
+        let (width, height) = image.dimensions();
+        ImageBuffer::from_fn(
+            width.next_multiple_of(4),
+            height.next_multiple_of(4),
+            |x, y| *image.get_pixel(x.min(width - 1), y.min(height - 1)),
+        )
+    }
+    match dyn_image {
+        DynamicImage::ImageLuma8(image) => DynamicImage::ImageLuma8(pad(image)),
+        DynamicImage::ImageLumaA8(image) => DynamicImage::ImageLumaA8(pad(image)),
+        DynamicImage::ImageRgba8(image) => DynamicImage::ImageRgba8(pad(image)),
+        DynamicImage::ImageRgba32F(image) => DynamicImage::ImageRgba32F(pad(image)),
+        dyn_image => dyn_image.clone(),
+    }
+}
+
+/// Compresses a surface in chunks of block rows on the task pool. Blocks are compressed
+/// independently and stored row by row, so each chunk writes its own range of `out` and the
+/// result is the same as compressing the surface at once. `compress` is called with the height
+/// of a chunk, its pixel data and its part of `out`.
+///
+/// This usually runs inside a task on the same pool. That can't starve the pool:
+/// `TaskPool::scope` runs the pool's tasks on the calling thread while it waits, and the chunks
+/// never wait on anything. If every other thread is busy, the calling thread compresses all the
+/// chunks itself, like the serial path.
+#[cfg(feature = "compress")]
+fn compress_block_rows(
+    width: u32,
+    height: u32,
+    bytes_per_pixel: u32,
+    data: &[u8],
+    out: &mut [u8],
+    compress: impl Fn(u32, &[u8], &mut [u8]) + Sync,
+) {
+    let pool = async_compute_task_pool();
+    let block_rows = height.div_ceil(4) as usize;
+    // Small levels aren't worth the overhead of spawning tasks
+    if pool.thread_num() <= 1 || block_rows < 2 || (width as u64) * (height as u64) < 256 * 256 {
+        compress(height, data, out);
+        return;
+    }
+    let row_len = width as usize * bytes_per_pixel as usize;
+    let rows_per_chunk = block_rows.div_ceil(pool.thread_num() * 2);
+    let data_chunk_len = rows_per_chunk * 4 * row_len;
+    let out_chunk_len = rows_per_chunk * out.len() / block_rows;
+    let compress = &compress;
+    pool.scope(|scope| {
+        for (data, out) in data
+            .chunks(data_chunk_len)
+            .zip(out.chunks_mut(out_chunk_len))
+        {
+            let chunk_height = (data.len() / row_len) as u32;
+            scope.spawn(async move { compress(chunk_height, data, out) });
+        }
+    });
+}
+
+/// Source to compressed format pairs used when compression is enabled (without `low_quality`).
+/// This is the table `bcn_equivalent_format_of_dyn_image` implements, useful for checking ahead of
+/// time which textures will be compressed. Any format not listed here is left uncompressed.
+pub fn compressible_formats() -> &'static [(TextureFormat, TextureFormat)] {
+    &[
+        (TextureFormat::R8Unorm, TextureFormat::Bc4RUnorm),
+        (TextureFormat::Rg8Unorm, TextureFormat::Bc5RgUnorm),
+        (TextureFormat::Rgba8Unorm, TextureFormat::Bc7RgbaUnorm),
+        (
+            TextureFormat::Rgba8UnormSrgb,
+            TextureFormat::Bc7RgbaUnormSrgb,
+        ),
+        (TextureFormat::Bgra8Unorm, TextureFormat::Bc7RgbaUnorm),
+        (
+            TextureFormat::Bgra8UnormSrgb,
+            TextureFormat::Bc7RgbaUnormSrgb,
+        ),
+        (TextureFormat::Rgba16Float, TextureFormat::Bc6hRgbUfloat),
+        (TextureFormat::Rgba32Float, TextureFormat::Bc6hRgbUfloat),
+    ]
+}
+
 /// If low_quality is set, only 0.5 byte/px formats will be used (BC1, BC4) unless alpha is being used (BC3)
+/// Keep in sync with `compressible_formats`.
+///
+/// | `dyn_image` | default            | `low_quality`                                    |
+/// |-------------|--------------------|--------------------------------------------------|
+/// | Luma8       | Bc4RUnorm          | Bc4RUnorm                                        |
+/// | LumaA8      | Bc5RgUnorm         | Bc1RgbaUnorm                                     |
+/// | Rgba8       | Bc7RgbaUnorm(Srgb) | Bc3RgbaUnorm(Srgb), Bc1RgbaUnorm(Srgb) if opaque |
+/// | Rgba32F     | Bc6hRgbUfloat      | Bc6hRgbUfloat                                    |
+///
+/// The sRGB variants are picked when `is_srgb` is set. BC6H has no alpha, and negative values are
+/// clamped to 0 since the encoder only supports the unsigned variant. Other variants, and images
+/// smaller than 4x4, return an error.
 pub fn bcn_equivalent_format_of_dyn_image(
     dyn_image: &DynamicImage,
     is_srgb: bool,
     low_quality: bool,
     has_alpha: bool,
-) -> anyhow::Result<TextureFormat> {
+) -> Result<TextureFormat, MipmapError> {
     if dyn_image.width() < 4 || dyn_image.height() < 4 {
-        return Err(anyhow!("Image size too small for BCn compression"));
+        return Err(MipmapError::TooSmallForCompression);
     }
     if low_quality {
         match dyn_image {
@@ -824,11 +5107,9 @@ pub fn bcn_equivalent_format_of_dyn_image(
                     TextureFormat::Bc1RgbaUnorm
                 }
             }),
+            DynamicImage::ImageRgba32F(_) => Ok(TextureFormat::Bc6hRgbUfloat),
             // Throw and error if conversion isn't supported
-            dyn_image => Err(anyhow!(
-                "Conversion into dynamic image not supported for {:?}.",
-                dyn_image
-            )),
+            dyn_image => Err(MipmapError::UnsupportedColorType(dyn_image.color())),
         }
     } else {
         match dyn_image {
@@ -839,50 +5120,165 @@ pub fn bcn_equivalent_format_of_dyn_image(
             } else {
                 TextureFormat::Bc7RgbaUnorm
             }),
+            DynamicImage::ImageRgba32F(_) => Ok(TextureFormat::Bc6hRgbUfloat),
             // Throw and error if conversion isn't supported
-            dyn_image => Err(anyhow!(
-                "Conversion into dynamic image not supported for {:?}.",
-                dyn_image
-            )),
+            dyn_image => Err(MipmapError::UnsupportedColorType(dyn_image.color())),
         }
     }
 }
 
+/// BC3 equivalent of a BC7 format, used for `use_bc3_for_rgba`. Other formats are unchanged.
+fn bc7_to_bc3(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::Bc7RgbaUnorm => TextureFormat::Bc3RgbaUnorm,
+        TextureFormat::Bc7RgbaUnormSrgb => TextureFormat::Bc3RgbaUnormSrgb,
+        format => format,
+    }
+}
+
+/// Returns the sRGB or linear variant of a BCn format.
+/// Errors if the format doesn't have a variant in the requested color space.
+pub fn bcn_format_with_color_space(
+    format: TextureFormat,
+    color_space: ColorSpace,
+) -> Result<TextureFormat, MipmapError> {
+    let new_format = color_space.apply_to_format(format);
+    if ColorSpace::of_format(new_format) != color_space {
+        return Err(MipmapError::NoColorSpaceVariant {
+            format,
+            color_space,
+        });
+    }
+    Ok(new_format)
+}
+
+/// Part of every cache key. Bump it when the generated data changes for the same settings, for
+/// example after fixing a filter, so existing cache entries are no longer used.
+#[cfg(feature = "compress")]
+const CACHE_VERSION: u32 = 1;
+
 /// Calculate the hash for the non-compressed non-mipmapped image.
+/// Every setting that changes the generated data has to be included, otherwise stale entries
+/// are loaded from the cache after the setting changes.
 #[cfg(feature = "compress")]
 fn calculate_hash(image: &Image, settings: &MipmapGeneratorSettings) -> u64 {
     let mut hasher = DefaultHasher::new();
+    CACHE_VERSION.hash(&mut hasher);
     image.data.hash(&mut hasher);
     if settings.low_quality {
         (934870234u32).hash(&mut hasher);
     }
+    if !settings.detect_opaque_alpha {
+        (57310286u32).hash(&mut hasher);
+    }
+    if settings.detect_grayscale {
+        (84617203u32).hash(&mut hasher);
+    }
+    if settings.base_scale > 1 {
+        (19283746u32, settings.base_scale.ilog2()).hash(&mut hasher);
+    }
     settings.compression.hash(&mut hasher);
-    match settings.filter_type {
-        FilterType::Nearest => (934870234u32).hash(&mut hasher),
-        FilterType::Triangle => (46345624u32).hash(&mut hasher),
-        FilterType::CatmullRom => (54676234u32).hash(&mut hasher),
-        FilterType::Gaussian => (623455643u32).hash(&mut hasher),
-        FilterType::Lanczos3 => (675856584u32).hash(&mut hasher),
+    settings
+        .post_downsample_sharpen
+        .map(f32::to_bits)
+        .hash(&mut hasher);
+    settings
+        .alpha_coverage_cutoff
+        .map(f32::to_bits)
+        .hash(&mut hasher);
+    filter_type_hash(settings.filter_type).hash(&mut hasher);
+    match settings.custom_filter {
+        Some(CustomFilter::Bilateral { range_sigma }) => {
+            (81736452u32, range_sigma.to_bits()).hash(&mut hasher);
+        }
+        Some(CustomFilter::PerChannel(filters)) => {
+            (20381745u32, filters.map(filter_type_hash)).hash(&mut hasher);
+        }
+        Some(CustomFilter::Kaiser { alpha }) => {
+            (61048297u32, alpha.to_bits()).hash(&mut hasher);
+        }
+        None => (),
     }
     image.texture_descriptor.hash(&mut hasher);
+    max_mip_count(image, settings).hash(&mut hasher);
+    settings.minimum_mip_resolution.hash(&mut hasher);
+    if settings.high_precision {
+        (23894571u32).hash(&mut hasher);
+    }
+    if settings.linear_color_downsample {
+        (48120973u32).hash(&mut hasher);
+    }
+    match settings.downsample_gamma {
+        GammaMode::Srgb => (93027416u32).hash(&mut hasher),
+        GammaMode::Custom(gamma) => (93027417u32, gamma.to_bits()).hash(&mut hasher),
+        GammaMode::Linear => (),
+    }
+    if settings.renormalize_normals {
+        (61298734u32).hash(&mut hasher);
+    }
+    if settings.use_bc3_for_rgba && !settings.low_quality {
+        (70923415u32).hash(&mut hasher);
+    }
+    if let Some(format) = settings.force_output_format {
+        (30498217u32, format).hash(&mut hasher);
+    }
+    if settings.compression_format != CompressionFormat::Auto {
+        (41827365u32, settings.compression_format).hash(&mut hasher);
+    }
+    if let Some(color_space) = settings.compressed_color_space {
+        (74019283u32, color_space).hash(&mut hasher);
+    }
+    if settings.compress_only {
+        (36571904u32).hash(&mut hasher);
+    }
+    if let Some(color_grade) = &settings.color_grade {
+        if ColorSpace::of_descriptor(&image.texture_descriptor) == ColorSpace::Srgb {
+            (52938164u32, color_grade.key()).hash(&mut hasher);
+        }
+    }
     hasher.finish()
 }
 
-/// Save raw image bytes to disk cache
+/// `FilterType` doesn't implement `Hash`.
+#[cfg(feature = "compress")]
+fn filter_type_hash(filter_type: FilterType) -> u32 {
+    match filter_type {
+        FilterType::Nearest => 934870234,
+        FilterType::Triangle => 46345624,
+        FilterType::CatmullRom => 54676234,
+        FilterType::Gaussian => 623455643,
+        FilterType::Lanczos3 => 675856584,
+    }
+}
+
+/// Save raw image bytes to disk cache, first making room for them if `max_bytes` is set.
 #[cfg(feature = "compress")]
-fn save_to_cache(hash: u64, bytes: &[u8], cache_dir: &Path) -> std::io::Result<()> {
+fn save_to_cache(
+    hash: u64,
+    bytes: &[u8],
+    cache_dir: &Path,
+    max_bytes: Option<u64>,
+) -> std::io::Result<()> {
     if !cache_dir.exists() {
         fs::create_dir(cache_dir)?;
     }
+    let encoded = zstd::encode_all(bytes, 0)?;
+    if let Some(max_bytes) = max_bytes {
+        // Other tasks can be trimming the same directory, so failing to make room isn't fatal
+        if let Err(e) = trim_cache(cache_dir, max_bytes.saturating_sub(encoded.len() as u64)) {
+            warn!("Failed to trim mipmap cache {:?}: {}", cache_dir, e);
+        }
+    }
     let file_path = cache_dir.join(format!("{:x}", hash));
     let mut file = File::create(file_path)?;
-    file.write_all(&zstd::encode_all(bytes, 0).unwrap())?;
+    file.write_all(&encoded)?;
     Ok(())
 }
 
-/// Load from disk cache for matching input hash
+/// Load from disk cache for matching input hash. Entries that fail to decode or don't have the
+/// `expected_len` (if given), like files truncated by an interrupted write, are treated as a miss.
 #[cfg(feature = "compress")]
-fn load_from_cache(hash: u64, cache_dir: &Path) -> Option<Vec<u8>> {
+fn load_from_cache(hash: u64, cache_dir: &Path, expected_len: Option<usize>) -> Option<Vec<u8>> {
     let file_path = cache_dir.join(format!("{:x}", hash));
     if !file_path.exists() {
         return None;
@@ -891,8 +5287,96 @@ fn load_from_cache(hash: u64, cache_dir: &Path) -> Option<Vec<u8>> {
         return None;
     };
     let mut cached_bytes = Vec::new();
-    if !file.read_to_end(&mut cached_bytes).is_ok() {
+    if file.read_to_end(&mut cached_bytes).is_err() {
         return None;
     };
-    zstd::decode_all(cached_bytes.as_slice()).ok()
+    // The modification time doubles as the last use for trim_cache. Not every platform allows
+    // this on a read only file, the entry is then just evicted sooner.
+    let _ = file.set_modified(std::time::SystemTime::now());
+    match zstd::decode_all(cached_bytes.as_slice()) {
+        Ok(data) => match expected_len {
+            Some(expected_len) if data.len() != expected_len => {
+                warn!(
+                    "Ignoring mipmap cache entry {:x}, it has {} bytes instead of {}",
+                    hash,
+                    data.len(),
+                    expected_len
+                );
+                None
+            }
+            _ => Some(data),
+        },
+        Err(e) => {
+            warn!("Ignoring corrupt mipmap cache entry {:x}: {}", hash, e);
+            None
+        }
+    }
+}
+
+/// A file in `compressed_image_data_cache_path`, see `cache_entries`.
+#[cfg(feature = "compress")]
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    /// Hash of the source image and the settings that affect the output, the file name in hex.
+    pub hash: u64,
+    pub path: std::path::PathBuf,
+    /// Size of the file on disk, the data is stored zstd compressed.
+    pub size_bytes: u64,
+    pub modified: std::time::SystemTime,
+}
+
+/// Lists the entries of a compressed image data cache directory, for building cache management
+/// on top. Files that aren't cache entries are skipped, and a directory that doesn't exist yet
+/// (it's only created once something is cached) has no entries.
+#[cfg(feature = "compress")]
+pub fn cache_entries(cache_dir: &Path) -> std::io::Result<Vec<CacheEntry>> {
+    if !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(cache_dir)? {
+        let dir_entry = dir_entry?;
+        let Some(hash) = dir_entry
+            .file_name()
+            .to_str()
+            .and_then(|name| u64::from_str_radix(name, 16).ok())
+        else {
+            continue;
+        };
+        let metadata = dir_entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        entries.push(CacheEntry {
+            hash,
+            path: dir_entry.path(),
+            size_bytes: metadata.len(),
+            modified: metadata.modified()?,
+        });
+    }
+    Ok(entries)
+}
+
+/// Deletes the least recently used entries of a compressed image data cache directory until
+/// the remaining entries take at most `max_bytes`. Entries are used when they're written or
+/// loaded, see `MipmapGeneratorSettings::cache_max_bytes`. Returns the number of bytes deleted.
+#[cfg(feature = "compress")]
+pub fn trim_cache(cache_dir: &Path, max_bytes: u64) -> std::io::Result<u64> {
+    let mut entries = cache_entries(cache_dir)?;
+    let mut total: u64 = entries.iter().map(|entry| entry.size_bytes).sum();
+    entries.sort_by_key(|entry| entry.modified);
+    let mut deleted = 0;
+    for entry in entries {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&entry.path) {
+            Ok(()) => deleted += entry.size_bytes,
+            // Already deleted by another task
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        total -= entry.size_bytes;
+    }
+    Ok(deleted)
 }
@@ -0,0 +1,586 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+        },
+    },
+};
+
+use crate::*;
+
+fn test_image(width: u32, height: u32, format: TextureFormat, data: Vec<u8>) -> Image {
+    test_array(width, height, 1, format, data)
+}
+
+fn test_array(width: u32, height: u32, layers: u32, format: TextureFormat, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Rgba8 image with a diagonal gradient in color and alpha.
+fn gradient_rgba8(width: u32, height: u32) -> Vec<u8> {
+    (0..height)
+        .flat_map(|y| {
+            (0..width).flat_map(move |x| {
+                let r = (x * 255 / (width - 1).max(1)) as u8;
+                let g = (y * 255 / (height - 1).max(1)) as u8;
+                [r, g, r / 2 + g / 2, 255 - r]
+            })
+        })
+        .collect()
+}
+
+fn chain_len(image: &Image) -> usize {
+    let descriptor = &image.texture_descriptor;
+    mip_chain_byte_size(
+        descriptor.size.width,
+        descriptor.size.height,
+        descriptor.format,
+        descriptor.mip_level_count,
+    ) * descriptor.size.depth_or_array_layers as usize
+}
+
+#[test]
+fn float_images_keep_their_format_through_kaiser_and_strips() {
+    let kaiser = MipmapGeneratorSettings {
+        custom_filter: Some(CustomFilter::Kaiser { alpha: 4.0 }),
+        ..default()
+    };
+    let cases = [
+        (64, 64, TextureFormat::Rgba32Float, &kaiser),
+        (64, 64, TextureFormat::Rgba16Float, &kaiser),
+        (
+            64,
+            1,
+            TextureFormat::Rgba32Float,
+            &MipmapGeneratorSettings::default(),
+        ),
+        (
+            64,
+            1,
+            TextureFormat::Rgba16Float,
+            &MipmapGeneratorSettings::default(),
+        ),
+    ];
+    for (width, height, format, settings) in cases {
+        let rgba32f: Vec<u8> = gradient_rgba8(width, height)
+            .into_iter()
+            .flat_map(|c| (c as f32 / 255.0).to_ne_bytes())
+            .collect();
+        let data = if format == TextureFormat::Rgba16Float {
+            f32_data_to_f16(&rgba32f)
+        } else {
+            rgba32f
+        };
+        let mut image = test_image(width, height, format, data);
+        generate_mips_texture(&mut image, settings, &mut 0).unwrap();
+        assert_eq!(image.texture_descriptor.format, format);
+        assert_eq!(image.texture_descriptor.mip_level_count, 7);
+        assert_eq!(
+            image.data.len(),
+            chain_len(&image),
+            "{format:?} {width}x{height}"
+        );
+    }
+}
+
+#[test]
+fn kaiser_keeps_hdr_values_above_one() {
+    let settings = MipmapGeneratorSettings {
+        custom_filter: Some(CustomFilter::Kaiser { alpha: 4.0 }),
+        ..default()
+    };
+    let texel = [4.0f32, 2.0, 1.5, 1.0];
+    let data: Vec<u8> = texel
+        .repeat(16 * 16)
+        .into_iter()
+        .flat_map(f32::to_ne_bytes)
+        .collect();
+    let mut image = test_image(16, 16, TextureFormat::Rgba32Float, data);
+    generate_mips_texture(&mut image, &settings, &mut 0).unwrap();
+    let smallest = extract_mip_level(&image, image.texture_descriptor.mip_level_count).unwrap();
+    let values: Vec<f32> = smallest
+        .data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+        .collect();
+    for (value, expected) in values.iter().zip(texel) {
+        assert!((value - expected).abs() < 1e-3, "{values:?}");
+    }
+}
+
+#[test]
+fn cubemaps_need_cubemap_seamless() {
+    let face = gradient_rgba8(16, 16);
+    let mut cubemap = test_array(16, 16, 6, TextureFormat::Rgba8Unorm, face.repeat(6));
+    assert!(is_cubemap(&cubemap));
+    assert!(matches!(
+        check_image_compatible(&cubemap, &MipmapGeneratorSettings::default()),
+        Err(MipmapError::CubemapNotEnabled)
+    ));
+
+    // An explicit D2Array view makes it a plain array
+    let mut array = cubemap.clone();
+    array.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..default()
+    });
+    assert!(!is_cubemap(&array));
+    assert!(check_image_compatible(&array, &MipmapGeneratorSettings::default()).is_ok());
+
+    let settings = MipmapGeneratorSettings {
+        cubemap_seamless: true,
+        ..default()
+    };
+    generate_mips_texture(&mut cubemap, &settings, &mut 0).unwrap();
+    assert_eq!(cubemap.texture_descriptor.size.depth_or_array_layers, 6);
+    assert_eq!(cubemap.texture_descriptor.mip_level_count, 5);
+    assert_eq!(cubemap.data.len(), chain_len(&cubemap));
+    // Every face was generated from identical data
+    let face_len = cubemap.data.len() / 6;
+    for face in cubemap.data.chunks_exact(face_len) {
+        assert_eq!(face, &cubemap.data[..face_len]);
+    }
+}
+
+#[test]
+fn releasing_mips_keeps_every_layer() {
+    let size = 16;
+    let data: Vec<u8> = (0..3u8)
+        .flat_map(|layer| {
+            gradient_rgba8(size, size)
+                .into_iter()
+                .map(move |c| c ^ layer)
+        })
+        .collect();
+    let mut array = test_array(size, size, 3, TextureFormat::Rgba8Unorm, data.clone());
+    generate_mips_texture(&mut array, &MipmapGeneratorSettings::default(), &mut 0).unwrap();
+    assert_eq!(array.texture_descriptor.mip_level_count, 5);
+
+    release_mips(&mut array);
+    assert_eq!(array.texture_descriptor.mip_level_count, 1);
+    assert_eq!(array.texture_descriptor.size.depth_or_array_layers, 3);
+    assert_eq!(array.data, data);
+}
+
+#[test]
+fn settings_override_follows_the_global_settings() {
+    let settings_override = MipmapSettingsOverride {
+        compression: Some(true),
+        filter_type: Some(FilterType::Nearest),
+        ..default()
+    };
+    let mut settings = MipmapGeneratorSettings::default();
+    let resolved = settings_override.apply_to(&settings);
+    assert!(matches!(
+        resolved.compression,
+        Some(CompressionSpeed::UltraFast)
+    ));
+    assert_eq!(resolved.filter_type, FilterType::Nearest);
+    assert_eq!(resolved.minimum_mip_resolution, 1);
+
+    // Fields that aren't overridden pick up changes to the global settings
+    settings.compression = Some(CompressionSpeed::Slow);
+    settings.minimum_mip_resolution = 16;
+    let resolved = settings_override.apply_to(&settings);
+    assert!(matches!(resolved.compression, Some(CompressionSpeed::Slow)));
+    assert_eq!(resolved.filter_type, FilterType::Nearest);
+    assert_eq!(resolved.minimum_mip_resolution, 16);
+}
+
+#[test]
+fn bcn_format_for_each_dynamic_image_variant() {
+    use TextureFormat::*;
+    let (w, h) = (4, 8);
+    // (image, default linear, default sRGB, low_quality opaque linear, low_quality sRGB with alpha)
+    let supported = [
+        (
+            DynamicImage::new_luma8(w, h),
+            Bc4RUnorm,
+            Bc4RUnorm,
+            Bc4RUnorm,
+            Bc4RUnorm,
+        ),
+        (
+            DynamicImage::new_luma_a8(w, h),
+            Bc5RgUnorm,
+            Bc5RgUnorm,
+            Bc1RgbaUnorm,
+            Bc1RgbaUnorm,
+        ),
+        (
+            DynamicImage::new_rgba8(w, h),
+            Bc7RgbaUnorm,
+            Bc7RgbaUnormSrgb,
+            Bc1RgbaUnorm,
+            Bc3RgbaUnormSrgb,
+        ),
+        (
+            DynamicImage::new_rgba32f(w, h),
+            Bc6hRgbUfloat,
+            Bc6hRgbUfloat,
+            Bc6hRgbUfloat,
+            Bc6hRgbUfloat,
+        ),
+    ];
+    for (image, linear, srgb, low_quality_opaque, low_quality_alpha) in supported {
+        let format = |is_srgb, low_quality, has_alpha| {
+            bcn_equivalent_format_of_dyn_image(&image, is_srgb, low_quality, has_alpha).unwrap()
+        };
+        assert_eq!(format(false, false, true), linear, "{:?}", image.color());
+        assert_eq!(format(true, false, true), srgb, "{:?}", image.color());
+        assert_eq!(
+            format(false, true, false),
+            low_quality_opaque,
+            "{:?}",
+            image.color()
+        );
+        assert_eq!(
+            format(true, true, true),
+            low_quality_alpha,
+            "{:?}",
+            image.color()
+        );
+    }
+    // The low_quality Rgba8 variants not covered above
+    let rgba8 = DynamicImage::new_rgba8(w, h);
+    assert_eq!(
+        bcn_equivalent_format_of_dyn_image(&rgba8, true, true, false).unwrap(),
+        Bc1RgbaUnormSrgb
+    );
+    assert_eq!(
+        bcn_equivalent_format_of_dyn_image(&rgba8, false, true, true).unwrap(),
+        Bc3RgbaUnorm
+    );
+
+    let unsupported = [
+        DynamicImage::new_rgb8(w, h),
+        DynamicImage::new_luma16(w, h),
+        DynamicImage::new_luma_a16(w, h),
+        DynamicImage::new_rgb16(w, h),
+        DynamicImage::new_rgba16(w, h),
+        DynamicImage::new_rgb32f(w, h),
+    ];
+    for image in unsupported {
+        for (is_srgb, low_quality) in [(false, false), (true, false), (false, true), (true, true)] {
+            assert!(
+                matches!(
+                    bcn_equivalent_format_of_dyn_image(&image, is_srgb, low_quality, true),
+                    Err(MipmapError::UnsupportedColorType(color)) if color == image.color()
+                ),
+                "{:?}",
+                image.color()
+            );
+        }
+    }
+
+    for (w, h) in [(3, 4), (4, 3), (1, 1)] {
+        for low_quality in [false, true] {
+            assert!(matches!(
+                bcn_equivalent_format_of_dyn_image(
+                    &DynamicImage::new_rgba8(w, h),
+                    false,
+                    low_quality,
+                    true
+                ),
+                Err(MipmapError::TooSmallForCompression)
+            ));
+        }
+    }
+}
+
+#[test]
+fn extract_mip_level_matches_generate_mips() {
+    let size = 2048;
+    let data = gradient_rgba8(size, size);
+    let settings = MipmapGeneratorSettings::default();
+    let mut image = test_image(size, size, TextureFormat::Rgba8Unorm, data.clone());
+    generate_mips_texture(&mut image, &settings, &mut 0).unwrap();
+    let mip_count = image.texture_descriptor.mip_level_count;
+    assert_eq!(mip_count, 12);
+
+    let mut dyn_image = DynamicImage::ImageRgba8(ImageBuffer::from_raw(size, size, data).unwrap());
+    let expected = generate_mips(
+        &mut dyn_image,
+        ColorSpace::Linear,
+        true,
+        mip_count,
+        &settings,
+    );
+    let mut offset = 0;
+    for mip_level in 1..=mip_count {
+        let level = extract_mip_level(&image, mip_level).unwrap();
+        let level_size = size >> (mip_level - 1);
+        let descriptor = &level.texture_descriptor;
+        assert_eq!(descriptor.size.width, level_size);
+        assert_eq!(descriptor.size.height, level_size);
+        assert_eq!(descriptor.mip_level_count, 1);
+        assert_eq!(descriptor.format, TextureFormat::Rgba8Unorm);
+        let len = (level_size * level_size * 4) as usize;
+        assert!(
+            level.data == expected[offset..offset + len],
+            "level {mip_level}"
+        );
+        offset += len;
+    }
+    assert_eq!(offset, expected.len());
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn extract_mip_level_from_bc7_chain() {
+    // Not a multiple of 4 below the first level, so the smaller levels are padded to whole blocks
+    let (width, height) = (40, 24);
+    let mut image = test_image(
+        width,
+        height,
+        TextureFormat::Rgba8Unorm,
+        gradient_rgba8(width, height),
+    );
+    image.asset_usage = RenderAssetUsages::RENDER_WORLD;
+    let settings = MipmapGeneratorSettings {
+        compression: Some(CompressionSpeed::UltraFast),
+        ..default()
+    };
+    generate_mips_texture(&mut image, &settings, &mut 0).unwrap();
+    assert_eq!(image.texture_descriptor.format, TextureFormat::Bc7RgbaUnorm);
+    // 40x24, 20x12 and 10x6, 5x3 would be below the 4 pixels compression needs
+    assert_eq!(image.texture_descriptor.mip_level_count, 3);
+
+    let expected_lens = [10 * 6 * 16, 5 * 3 * 16, 3 * 2 * 16];
+    let mut data = Vec::new();
+    for (mip_level, expected_len) in (1..).zip(expected_lens) {
+        let level = extract_mip_level(&image, mip_level).unwrap();
+        let descriptor = &level.texture_descriptor;
+        assert_eq!(descriptor.format, TextureFormat::Bc7RgbaUnorm);
+        assert_eq!(descriptor.size.width, width >> (mip_level - 1));
+        assert_eq!(descriptor.size.height, height >> (mip_level - 1));
+        assert_eq!(level.data.len(), expected_len, "level {mip_level}");
+        assert_eq!(level.asset_usage, image.asset_usage);
+        data.extend(level.data);
+    }
+    assert_eq!(data, image.data);
+}
+
+#[test]
+fn extract_mip_level_bounds() {
+    let size = 16;
+    let mut image = test_image(
+        size,
+        size,
+        TextureFormat::Rgba8Unorm,
+        gradient_rgba8(size, size),
+    );
+    generate_mips_texture(&mut image, &MipmapGeneratorSettings::default(), &mut 0).unwrap();
+    let mip_count = image.texture_descriptor.mip_level_count;
+    assert_eq!(mip_count, 5);
+
+    let first = extract_mip_level(&image, 1).unwrap();
+    assert_eq!(first.texture_descriptor.size, image.texture_descriptor.size);
+    assert!(first.data == image.data[..(size * size * 4) as usize]);
+
+    let last = extract_mip_level(&image, mip_count).unwrap();
+    assert_eq!(
+        (
+            last.texture_descriptor.size.width,
+            last.texture_descriptor.size.height
+        ),
+        (1, 1)
+    );
+    assert!(last.data == image.data[image.data.len() - 4..]);
+
+    for mip_level in [0, mip_count + 1] {
+        assert!(matches!(
+            extract_mip_level(&image, mip_level),
+            Err(MipmapError::MipLevelOutOfRange { mip_level: level, available })
+                if level == mip_level && available == mip_count
+        ));
+    }
+
+    // The last level is missing, the others can still be extracted
+    let mut truncated = image.clone();
+    truncated.data.truncate(image.data.len() - 4);
+    assert!(extract_mip_level(&truncated, mip_count - 1).is_ok());
+    assert!(matches!(
+        extract_mip_level(&truncated, mip_count),
+        Err(MipmapError::DataTooShort { len, needed, mip_level })
+            if len == image.data.len() - 4 && needed == image.data.len() && mip_level == mip_count
+    ));
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn bc6h_clamps_negative_values() {
+    let hdr = |r: f32| {
+        DynamicImage::ImageRgba32F(ImageBuffer::from_fn(8, 8, |x, _| {
+            Rgba([if x < 4 { r } else { 2.0 }, 0.5, 1.0, 1.0])
+        }))
+    };
+    let compress = |image: &DynamicImage| {
+        bcn_compress_dyn_image(CompressionSpeed::UltraFast, image, false, false, false).unwrap()
+    };
+    assert_eq!(compress(&hdr(-3.0)), compress(&hdr(0.0)));
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn compression_format_falls_back_for_other_sources() {
+    let gray: Vec<u8> = (0..16 * 16).map(|i| i as u8).collect();
+    let cases = [
+        (CompressionFormat::Bc1, TextureFormat::Bc1RgbaUnormSrgb),
+        (CompressionFormat::Bc3, TextureFormat::Bc3RgbaUnormSrgb),
+        (CompressionFormat::Bc7, TextureFormat::Bc7RgbaUnormSrgb),
+    ];
+    for (compression_format, expected) in cases {
+        let settings = MipmapGeneratorSettings {
+            compression: Some(CompressionSpeed::UltraFast),
+            compression_format,
+            low_quality: compression_format == CompressionFormat::Bc7,
+            ..default()
+        };
+        let mut rgba = test_image(
+            16,
+            16,
+            TextureFormat::Rgba8UnormSrgb,
+            gradient_rgba8(16, 16),
+        );
+        generate_mips_texture(&mut rgba, &settings, &mut 0).unwrap();
+        assert_eq!(rgba.texture_descriptor.format, expected);
+        assert_eq!(rgba.data.len(), chain_len(&rgba));
+
+        // Not an Rgba8 source, the inferred format is used instead of failing
+        let mut r8 = test_image(16, 16, TextureFormat::R8Unorm, gray.clone());
+        generate_mips_texture(&mut r8, &settings, &mut 0).unwrap();
+        assert_eq!(r8.texture_descriptor.format, TextureFormat::Bc4RUnorm);
+
+        // Too small to compress
+        let mut small = test_image(2, 2, TextureFormat::Rgba8Unorm, gradient_rgba8(2, 2));
+        generate_mips_texture(&mut small, &settings, &mut 0).unwrap();
+        assert_eq!(small.texture_descriptor.format, TextureFormat::Rgba8Unorm);
+    }
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn cache_key_covers_settings() {
+    let image = test_image(16, 16, TextureFormat::Rgba8Unorm, gradient_rgba8(16, 16));
+    let settings = MipmapGeneratorSettings {
+        compression: Some(CompressionSpeed::UltraFast),
+        ..default()
+    };
+    let base = calculate_hash(&image, &settings);
+    let changed = [
+        MipmapGeneratorSettings {
+            filter_type: FilterType::Lanczos3,
+            ..settings.clone()
+        },
+        MipmapGeneratorSettings {
+            compressed_color_space: Some(ColorSpace::Srgb),
+            ..settings.clone()
+        },
+        MipmapGeneratorSettings {
+            compress_only: true,
+            ..settings.clone()
+        },
+        MipmapGeneratorSettings {
+            compression_format: CompressionFormat::Bc3,
+            ..settings.clone()
+        },
+    ];
+    for changed in changed {
+        assert_ne!(calculate_hash(&image, &changed), base);
+    }
+    assert_eq!(calculate_hash(&image, &settings.clone()), base);
+
+    // Each filter gets its own cache file
+    let cache_dir =
+        std::env::temp_dir().join(format!("mipmap_cache_key_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    for filter_type in [
+        FilterType::Triangle,
+        FilterType::Lanczos3,
+        FilterType::Triangle,
+    ] {
+        let settings = MipmapGeneratorSettings {
+            filter_type,
+            compressed_image_data_cache_path: Some(cache_dir.clone()),
+            ..settings.clone()
+        };
+        generate_mips_texture(&mut image.clone(), &settings, &mut 0).unwrap();
+    }
+    let entries = cache_entries(&cache_dir).unwrap();
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+    assert_eq!(entries.len(), 2);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn array_cache_per_layer_and_whole() {
+    let size = 16;
+    let layers = |seed: u8| {
+        let mut data = Vec::new();
+        for layer in 0..3u8 {
+            data.extend(gradient_rgba8(size, size).iter().map(|c| c ^ layer ^ seed));
+        }
+        test_array(size, size, 3, TextureFormat::Rgba8Unorm, data)
+    };
+    // Changes only the last layer
+    let mut changed = layers(0);
+    let last = changed.data.len() - 4;
+    changed.data[last] ^= 0xFF;
+
+    for (per_layer_cache, base_scale) in [(true, 1), (false, 1), (true, 2), (false, 2)] {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "mipmap_array_cache_test_{per_layer_cache}_{base_scale}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let settings = MipmapGeneratorSettings {
+            compression: Some(CompressionSpeed::UltraFast),
+            compressed_image_data_cache_path: Some(cache_dir.clone()),
+            per_layer_cache,
+            base_scale,
+            ..default()
+        };
+        let mut first = layers(0);
+        generate_mips_texture(&mut first, &settings, &mut 0).unwrap();
+        let entries_after_first = cache_entries(&cache_dir).unwrap().len();
+        // Loaded from the cache, with the same result and nothing written again
+        let mut again = layers(0);
+        let mut added_cache_size = 0;
+        generate_mips_texture(&mut again, &settings, &mut added_cache_size).unwrap();
+        assert_eq!(again.texture_descriptor, first.texture_descriptor);
+        assert_eq!(again.data, first.data);
+        assert_eq!(added_cache_size, 0);
+        assert_eq!(
+            cache_entries(&cache_dir).unwrap().len(),
+            entries_after_first
+        );
+        let mut changed = changed.clone();
+        generate_mips_texture(&mut changed, &settings, &mut 0).unwrap();
+        let entries_after_change = cache_entries(&cache_dir).unwrap().len();
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+
+        assert_eq!(first.texture_descriptor.format, TextureFormat::Bc7RgbaUnorm);
+        assert_eq!(first.texture_descriptor.size.width, size / base_scale);
+        assert_eq!(first.texture_descriptor.size.depth_or_array_layers, 3);
+        assert_eq!(first.data.len(), chain_len(&first));
+        if per_layer_cache {
+            // One entry per layer, the two unchanged layers are reused
+            assert_eq!((entries_after_first, entries_after_change), (3, 4));
+        } else {
+            assert_eq!((entries_after_first, entries_after_change), (1, 2));
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Reads generated mip levels back from the GPU and compares them with the CPU data, to catch
+//! upload and layout mismatches on real hardware. Intended for tests and CI, enabled with the
+//! `gpu_validation` feature.
+
+use std::sync::{mpsc::channel, Arc, Mutex};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+        Extract, Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::extract_mip_range;
+
+/// Compares the mip levels listed in [`MipmapValidationRequests`] once they're uploaded, results
+/// are collected in [`MipmapValidationResults`]. Requires the render plugins from `DefaultPlugins`.
+pub struct MipmapValidationPlugin;
+impl Plugin for MipmapValidationPlugin {
+    fn build(&self, app: &mut App) {
+        let results = MipmapValidationResults::default();
+        app.init_resource::<MipmapValidationRequests>()
+            .init_resource::<ReadyValidations>()
+            .insert_resource(results.clone())
+            .add_systems(PostUpdate, prepare_mip_validations);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(results)
+            .init_resource::<PendingValidations>()
+            .add_systems(ExtractSchedule, extract_mip_validations)
+            .add_systems(Render, read_back_mip_levels.in_set(RenderSet::Cleanup));
+    }
+}
+
+/// Mip levels to read back and compare. A request waits until its image has a mip chain that
+/// includes the level, then it's removed and its result added to [`MipmapValidationResults`].
+///
+/// The expected data comes from the main world image, so images need to keep it
+/// (`RenderAssetUsages::MAIN_WORLD`). Images generated by `GpuMipmapGeneratorPlugin` don't have
+/// their lower levels on the CPU and can't be validated. `COPY_SRC` is added to the texture usage
+/// of requested images, which re-uploads them once.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MipmapValidationRequests(pub Vec<MipmapValidationRequest>);
+
+#[derive(Clone, Copy, Debug)]
+pub struct MipmapValidationRequest {
+    pub image: AssetId<Image>,
+    /// 0 based, like `extract_mip_range`.
+    pub mip_level: u32,
+    /// Largest difference allowed between a byte read back and the CPU data.
+    pub tolerance: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct MipmapValidationResult {
+    pub image: AssetId<Image>,
+    pub mip_level: u32,
+    /// Largest difference between a byte read back and the CPU data.
+    pub max_difference: u8,
+    /// Set if the level couldn't be compared, for example because the readback failed.
+    pub error: Option<String>,
+    pub passed: bool,
+}
+
+/// Results of the finished validations. Shared with the render world, clones see the same results.
+#[derive(Resource, Clone, Default)]
+pub struct MipmapValidationResults(Arc<Mutex<Vec<MipmapValidationResult>>>);
+
+impl MipmapValidationResults {
+    pub fn results(&self) -> Vec<MipmapValidationResult> {
+        self.0
+            .lock()
+            .map(|results| results.clone())
+            .unwrap_or_default()
+    }
+
+    /// Panics listing every failed validation, for use at the end of a test.
+    pub fn assert_passed(&self) {
+        let failed = self
+            .results()
+            .into_iter()
+            .filter(|result| !result.passed)
+            .collect::<Vec<_>>();
+        assert!(
+            failed.is_empty(),
+            "Mip level validation failed: {failed:#?}"
+        );
+    }
+
+    fn push(&self, result: MipmapValidationResult) {
+        if let Ok(mut results) = self.0.lock() {
+            results.push(result);
+        }
+    }
+}
+
+/// A level ready to be read back, with the data it's expected to contain.
+#[derive(Clone)]
+struct MipValidation {
+    request: MipmapValidationRequest,
+    format: TextureFormat,
+    size: Extent3d,
+    expected: Vec<u8>,
+}
+
+/// Levels that became ready this frame, moved to the render world during extraction.
+#[derive(Resource, Default)]
+struct ReadyValidations(Vec<MipValidation>);
+
+/// Render world levels waiting for their `GpuImage` to include the level.
+#[derive(Resource, Default)]
+struct PendingValidations(Vec<MipValidation>);
+
+fn prepare_mip_validations(
+    mut requests: ResMut<MipmapValidationRequests>,
+    mut ready: ResMut<ReadyValidations>,
+    mut images: ResMut<Assets<Image>>,
+    results: Res<MipmapValidationResults>,
+) {
+    ready.0.clear();
+    requests.retain(|request| {
+        // Check with get() first so images that aren't ready don't trigger change detection
+        let Some(image) = images.get(request.image) else {
+            return true;
+        };
+        if image.texture_descriptor.mip_level_count <= request.mip_level {
+            return true;
+        }
+        if !image
+            .texture_descriptor
+            .usage
+            .contains(TextureUsages::COPY_SRC)
+        {
+            if let Some(image) = images.get_mut(request.image) {
+                image.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+            }
+            return true;
+        }
+        match extract_mip_range(image, request.mip_level, 1) {
+            Ok(level) => ready.0.push(MipValidation {
+                request: *request,
+                format: level.texture_descriptor.format,
+                size: level.texture_descriptor.size,
+                expected: level.data,
+            }),
+            Err(e) => results.push(MipmapValidationResult {
+                image: request.image,
+                mip_level: request.mip_level,
+                max_difference: 0,
+                error: Some(e.to_string()),
+                passed: false,
+            }),
+        }
+        false
+    });
+}
+
+fn extract_mip_validations(
+    mut pending: ResMut<PendingValidations>,
+    ready: Extract<Res<ReadyValidations>>,
+) {
+    pending.0.extend(ready.0.iter().cloned());
+}
+
+/// Copies each pending level into a buffer and waits for it to be mapped. This blocks the render
+/// world, which is fine for the tests this is meant for.
+fn read_back_mip_levels(
+    mut pending: ResMut<PendingValidations>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    results: Res<MipmapValidationResults>,
+) {
+    pending.0.retain(|validation| {
+        let request = validation.request;
+        let Some(gpu_image) = gpu_images.get(request.image) else {
+            return true;
+        };
+        // Still the previous upload, wait for the one with the generated chain
+        if gpu_image.mip_level_count <= request.mip_level
+            || gpu_image.texture_format != validation.format
+        {
+            return true;
+        }
+        let (max_difference, error) = match read_back_level(
+            &render_device,
+            &render_queue,
+            &gpu_image.texture,
+            validation,
+        ) {
+            Ok(data) => (
+                data.iter()
+                    .zip(&validation.expected)
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap_or(0),
+                None,
+            ),
+            Err(e) => (0, Some(e)),
+        };
+        results.push(MipmapValidationResult {
+            image: request.image,
+            mip_level: request.mip_level,
+            max_difference,
+            passed: error.is_none() && max_difference <= request.tolerance,
+            error,
+        });
+        false
+    });
+}
+
+/// Returns the tightly packed data of a mip level, in the same layout as `Image::data`.
+fn read_back_level(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    texture: &Texture,
+    validation: &MipValidation,
+) -> Result<Vec<u8>, String> {
+    let format = validation.format;
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(None)
+        .ok_or_else(|| format!("{format:?} can't be read back."))?;
+    let blocks_x = validation.size.width.div_ceil(block_width);
+    let blocks_y = validation.size.height.div_ceil(block_height);
+    let row_bytes = (blocks_x * block_size) as usize;
+    let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("mipmap_generator_validation_buffer"),
+        size: (padded_row_bytes * blocks_y as usize) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("mipmap_generator_validation"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: validation.request.mip_level,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes as u32),
+                rows_per_image: None,
+            },
+        },
+        // Copies of block compressed levels have to cover whole blocks
+        Extent3d {
+            width: blocks_x * block_width,
+            height: blocks_y * block_height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = channel();
+    render_device.map_buffer(&slice, MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    render_device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let data = slice
+        .get_mapped_range()
+        .chunks(padded_row_bytes)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect();
+    buffer.unmap();
+    Ok(data)
+}